@@ -0,0 +1,335 @@
+use colored::*;
+use elm_dedup_project::{
+    corpus::{self, subdirectories, version_key},
+    error::Error,
+    package::PackageId,
+};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path};
+
+#[derive(Deserialize)]
+struct Module {
+    name: String,
+    #[serde(default)]
+    unions: Vec<Union>,
+    #[serde(default)]
+    aliases: Vec<Alias>,
+    #[serde(default)]
+    values: Vec<Value>,
+    #[serde(default)]
+    binops: Vec<Value>,
+}
+
+#[derive(Deserialize)]
+struct Union {
+    name: String,
+    args: Vec<String>,
+    cases: Vec<(String, Vec<String>)>,
+}
+
+#[derive(Deserialize)]
+struct Alias {
+    name: String,
+    args: Vec<String>,
+    #[serde(rename = "type")]
+    tipe: String,
+}
+
+#[derive(Deserialize)]
+struct Value {
+    name: String,
+    #[serde(rename = "type")]
+    tipe: String,
+}
+
+/// Every exposed module, mapping each of its exposed items to its signature
+type Api = BTreeMap<String, BTreeMap<String, String>>;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Bump {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl Bump {
+    fn between(from: &str, to: &str) -> Bump {
+        let (from, to) = (version_key(from), version_key(to));
+        if from.first() != to.first() {
+            Bump::Major
+        } else if from.get(1) != to.get(1) {
+            Bump::Minor
+        } else {
+            Bump::Patch
+        }
+    }
+}
+
+#[derive(Default)]
+struct Diff {
+    added: Vec<String>,
+    removed: Vec<String>,
+    changed: Vec<(String, String, String)>,
+}
+
+impl Diff {
+    /// The bump Elm's semver rules require: removing or changing anything is major, adding is minor
+    fn required_bump(&self) -> Bump {
+        if !self.removed.is_empty() || !self.changed.is_empty() {
+            Bump::Major
+        } else if !self.added.is_empty() {
+            Bump::Minor
+        } else {
+            Bump::Patch
+        }
+    }
+}
+
+fn load_api(path: &str) -> Result<Api, Error> {
+    let modules: Vec<Module> = serde_json::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| format!("Can't parse {path}: {e}"))?;
+
+    let mut api: Api = BTreeMap::new();
+    for module in modules {
+        let items: &mut BTreeMap<String, String> = api.entry(module.name).or_default();
+        for union in module.unions {
+            let cases: Vec<String> = union
+                .cases
+                .iter()
+                .map(|(name, args)| {
+                    std::iter::once(name.clone())
+                        .chain(args.iter().map(|arg| format!("({arg})")))
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                })
+                .collect();
+            let head: String = std::iter::once(union.name.clone())
+                .chain(union.args)
+                .collect::<Vec<String>>()
+                .join(" ");
+            let signature: String = if cases.is_empty() {
+                format!("type {head}")
+            } else {
+                format!("type {head} = {}", cases.join(" | "))
+            };
+            items.insert(union.name, signature);
+        }
+        for alias in module.aliases {
+            let head: String = std::iter::once(alias.name.clone())
+                .chain(alias.args)
+                .collect::<Vec<String>>()
+                .join(" ");
+            items.insert(alias.name, format!("type alias {head} = {}", alias.tipe));
+        }
+        for value in module.values {
+            items.insert(
+                value.name.clone(),
+                format!("{} : {}", value.name, value.tipe),
+            );
+        }
+        for binop in module.binops {
+            let name: String = format!("({})", binop.name);
+            items.insert(name.clone(), format!("{name} : {}", binop.tipe));
+        }
+    }
+    Ok(api)
+}
+
+fn diff(from: &Api, to: &Api) -> Diff {
+    let mut result: Diff = Diff::default();
+
+    for (module, from_items) in from {
+        let Some(to_items) = to.get(module) else {
+            result.removed.push(module.clone());
+            continue;
+        };
+        for (name, signature) in from_items {
+            match to_items.get(name) {
+                None => result.removed.push(format!("{module}.{name}")),
+                Some(new_signature) if new_signature != signature => result.changed.push((
+                    format!("{module}.{name}"),
+                    signature.clone(),
+                    new_signature.clone(),
+                )),
+                Some(_) => {}
+            }
+        }
+    }
+
+    for (module, to_items) in to {
+        match from.get(module) {
+            None => result.added.push(module.clone()),
+            Some(from_items) => {
+                for name in to_items.keys() {
+                    if !from_items.contains_key(name) {
+                        result.added.push(format!("{module}.{name}"));
+                    }
+                }
+            }
+        }
+    }
+
+    result
+}
+
+fn main() -> Result<(), Error> {
+    let mut summary_only: bool = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--summary" => summary_only = true,
+            _ => return Err(format!("Unknown argument: {arg}").into()),
+        }
+    }
+
+    corpus::check_root()?;
+    let docs: String = corpus::docs_root();
+    if !Path::new(&docs).exists() {
+        return Err(format!("No {docs} directory, run download-repos --docs first").into());
+    }
+
+    let mut steps: BTreeMap<Bump, usize> = BTreeMap::new();
+    let mut violations: usize = 0;
+
+    for author in subdirectories(&docs)? {
+        for name in subdirectories(&format!("{docs}/{author}"))? {
+            let package_name: String = format!("{author}/{name}");
+            let mut versions: Vec<PackageId> = subdirectories(&format!("{docs}/{package_name}"))?
+                .into_iter()
+                .map(|version| PackageId {
+                    author: author.clone(),
+                    name: name.clone(),
+                    version,
+                })
+                .filter(|id| Path::new(&id.docs_path()).join("docs.json").exists())
+                .collect();
+            versions.sort_by_key(|id| version_key(&id.version));
+
+            let mut previous: Option<(String, Api)> = None;
+            for id in versions {
+                let api: Api = load_api(&format!("{}/docs.json", id.docs_path()))?;
+                let version: String = id.version;
+                if let Some((previous_version, previous_api)) = &previous {
+                    let diff: Diff = diff(previous_api, &api);
+                    let required: Bump = diff.required_bump();
+                    let actual: Bump = Bump::between(previous_version, &version);
+                    *steps.entry(required).or_default() += 1;
+
+                    let headline: String = format!(
+                        "{} {} -> {}: {:?}",
+                        package_name.blue(),
+                        previous_version,
+                        version,
+                        required
+                    );
+                    if actual < required {
+                        violations += 1;
+                        println!(
+                            "{headline} {}",
+                            format!("(semver violation: released as {actual:?})").red()
+                        );
+                    } else if !summary_only {
+                        println!("{headline}");
+                    }
+
+                    if !summary_only {
+                        for item in &diff.added {
+                            println!("  {} {item}", "+".green());
+                        }
+                        for item in &diff.removed {
+                            println!("  {} {item}", "-".red());
+                        }
+                        for (item, from, to) in &diff.changed {
+                            println!("  {} {item}\n      {from}\n   -> {to}", "~".yellow());
+                        }
+                    }
+                }
+                previous = Some((version, api));
+            }
+        }
+    }
+
+    let total: usize = steps.values().sum();
+    let count = |bump: Bump| steps.get(&bump).copied().unwrap_or(0);
+    println!(
+        "{}",
+        format!(
+            "Compared {total} version steps: {} major, {} minor, {} patch, {violations} semver violations",
+            count(Bump::Major),
+            count(Bump::Minor),
+            count(Bump::Patch)
+        )
+        .green()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api(modules: &[(&str, &[(&str, &str)])]) -> Api {
+        modules
+            .iter()
+            .map(|(module, items)| {
+                let items: BTreeMap<String, String> = items
+                    .iter()
+                    .map(|(name, signature)| (name.to_string(), signature.to_string()))
+                    .collect();
+                (module.to_string(), items)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bumps_follow_the_first_differing_component() {
+        assert_eq!(Bump::between("1.0.0", "2.0.0"), Bump::Major);
+        assert_eq!(Bump::between("1.0.0", "1.1.0"), Bump::Minor);
+        assert_eq!(Bump::between("1.2.3", "1.2.4"), Bump::Patch);
+        assert_eq!(Bump::between("1.9.0", "1.10.0"), Bump::Minor);
+    }
+
+    #[test]
+    fn removing_a_module_is_major() {
+        let from: Api = api(&[("Foo", &[]), ("Foo.Internal", &[])]);
+        let to: Api = api(&[("Foo", &[])]);
+        let diff: Diff = diff(&from, &to);
+        assert_eq!(diff.removed, ["Foo.Internal"]);
+        assert_eq!(diff.required_bump(), Bump::Major);
+    }
+
+    #[test]
+    fn changing_a_union_case_is_major() {
+        let from: Api = api(&[("Foo", &[("Msg", "type Msg = Click | Hover")])]);
+        let to: Api = api(&[("Foo", &[("Msg", "type Msg = Click (Int) | Hover")])]);
+        let diff: Diff = diff(&from, &to);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].0, "Foo.Msg");
+        assert_eq!(diff.required_bump(), Bump::Major);
+    }
+
+    #[test]
+    fn adding_a_value_is_minor() {
+        let from: Api = api(&[("Foo", &[("a", "a : Int")])]);
+        let to: Api = api(&[("Foo", &[("a", "a : Int"), ("b", "b : Int")])]);
+        let diff: Diff = diff(&from, &to);
+        assert_eq!(diff.added, ["Foo.b"]);
+        assert!(diff.removed.is_empty() && diff.changed.is_empty());
+        assert_eq!(diff.required_bump(), Bump::Minor);
+    }
+
+    #[test]
+    fn an_unchanged_api_is_a_patch() {
+        let same: Api = api(&[("Foo", &[("a", "a : Int")])]);
+        assert_eq!(diff(&same, &same).required_bump(), Bump::Patch);
+    }
+
+    #[test]
+    fn a_patch_release_with_an_addition_is_a_violation() {
+        let from: Api = api(&[("Foo", &[])]);
+        let to: Api = api(&[("Foo", &[("b", "b : Int")])]);
+        let required: Bump = diff(&from, &to).required_bump();
+        assert!(Bump::between("1.0.0", "1.0.1") < required);
+        assert!(Bump::between("1.0.0", "1.1.0") >= required);
+    }
+}
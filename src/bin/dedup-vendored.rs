@@ -0,0 +1,281 @@
+use colored::*;
+use elm_dedup_project::{
+    corpus::{self, elm_files, version_key},
+    dedup::{self, content_of, Backend, Content, BACKENDS},
+    error::Error,
+    package::PackageId,
+    report::{self, csv_row, Format, FORMATS},
+};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+};
+
+struct Module {
+    package: PackageId,
+    name: String,
+    /// Relative to the checkout
+    file: String,
+    exposed: bool,
+    exact: Content,
+}
+
+/// The module name a file declares by its path, e.g. `src/List/Extra.elm` is `List.Extra`
+fn module_name(source_directory: &Path, file: &Path) -> String {
+    file.strip_prefix(source_directory)
+        .unwrap_or(file)
+        .with_extension("")
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<String>>()
+        .join(".")
+}
+
+/// The modules of a package version, each with the hash `backend` gives its source
+fn modules(
+    id: &PackageId,
+    min_size: u64,
+    backend: &dyn Backend,
+) -> Result<Vec<(Content, Module)>, Error> {
+    let path: String = id.path();
+    let exposed: BTreeSet<String> = corpus::exposed_modules(&path)?.into_iter().collect();
+
+    let mut result: Vec<(Content, Module)> = Vec::new();
+    for source_directory in corpus::source_directories(&path)? {
+        for file in elm_files(&source_directory)? {
+            let bytes: Vec<u8> = fs::read(&file)?;
+            let content: Content = backend.content(&file, &bytes);
+            if content.size < min_size {
+                continue;
+            }
+
+            let name: String = module_name(&source_directory, &file);
+            result.push((
+                content,
+                Module {
+                    package: id.clone(),
+                    exposed: exposed.contains(&name),
+                    name,
+                    file: file
+                        .strip_prefix(&path)
+                        .unwrap_or(&file)
+                        .to_string_lossy()
+                        .into_owned(),
+                    exact: content_of(&bytes),
+                },
+            ));
+        }
+    }
+    Ok(result)
+}
+
+/// Modules with the same content in several packages, at least one of which exposes it
+struct Cluster<'a> {
+    origins: Vec<&'a Module>,
+    /// The copies in packages that don't expose it, and whether they're byte-identical to an origin
+    vendored: Vec<(&'a Module, bool)>,
+}
+
+fn clusters(by_content: &BTreeMap<Content, Vec<Module>>) -> Vec<Cluster<'_>> {
+    let mut result: Vec<Cluster> = Vec::new();
+    for copies in by_content.values() {
+        let packages: BTreeSet<&PackageId> = copies.iter().map(|copy| &copy.package).collect();
+        if packages.len() < 2 {
+            continue;
+        }
+
+        // A package exposing the module is the likely origin of the copies that don't
+        let origins: Vec<&Module> = copies.iter().filter(|copy| copy.exposed).collect();
+        if origins.is_empty() {
+            continue;
+        }
+
+        let origin_packages: BTreeSet<&PackageId> =
+            origins.iter().map(|origin| &origin.package).collect();
+        let vendored: Vec<(&Module, bool)> = copies
+            .iter()
+            .filter(|copy| !copy.exposed && !origin_packages.contains(&copy.package))
+            .map(|copy| {
+                let is_exact: bool = origins.iter().any(|origin| origin.exact == copy.exact);
+                (copy, is_exact)
+            })
+            .collect();
+        result.push(Cluster { origins, vendored });
+    }
+    result
+}
+
+fn describe(module: &Module) -> Value {
+    json!({
+        "package": module.package.to_string(),
+        "module": module.name,
+        "file": module.file,
+    })
+}
+
+fn how(is_exact: bool) -> &'static str {
+    if is_exact {
+        "byte-identical"
+    } else {
+        "identical after normalization"
+    }
+}
+
+fn main() -> Result<(), Error> {
+    let mut min_size: u64 = 500;
+    let mut format: Format = Format::Text;
+    let mut backend: Box<dyn Backend> = Box::new(dedup::Normalized);
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--min-size" => {
+                min_size = args
+                    .next()
+                    .and_then(|size| size.parse().ok())
+                    .ok_or("--min-size needs a number of bytes".to_string())?
+            }
+            "--hash" => {
+                backend = args
+                    .next()
+                    .and_then(|name| dedup::backend(&name))
+                    .ok_or(format!("--hash needs one of {}", BACKENDS.join(", ")))?
+            }
+            "--format" => {
+                format = args
+                    .next()
+                    .and_then(|name| report::format(&name))
+                    .ok_or(format!("--format needs one of {}", FORMATS.join(", ")))?
+            }
+            _ => return Err(format!("Unknown argument: {arg}").into()),
+        }
+    }
+
+    // Only the latest version of each package: older ones would mostly find the package's own history
+    let mut latest: BTreeMap<String, PackageId> = BTreeMap::new();
+    for id in corpus::package_versions()? {
+        match latest.get(&id.package_name()) {
+            Some(known) if version_key(&known.version) >= version_key(&id.version) => {}
+            _ => {
+                latest.insert(id.package_name(), id);
+            }
+        }
+    }
+
+    eprintln!("{}", "Hashing modules".blue());
+    let hashed: Vec<Vec<(Content, Module)>> = latest
+        .into_par_iter()
+        .map(|(_, id)| match modules(&id, min_size, backend.as_ref()) {
+            Ok(modules) => modules,
+            Err(e) => {
+                eprintln!("{} {id}: {e:?}", "!!! Error hashing".red());
+                Vec::new()
+            }
+        })
+        .collect();
+
+    let mut by_content: BTreeMap<Content, Vec<Module>> = BTreeMap::new();
+    for (content, module) in hashed.into_iter().flatten() {
+        by_content.entry(content).or_default().push(module);
+    }
+
+    let clusters: Vec<Cluster> = clusters(&by_content);
+    match format {
+        Format::Text => print_text(&clusters),
+        Format::Json => {
+            let clusters: Vec<Value> = clusters
+                .iter()
+                .map(|cluster| {
+                    json!({
+                        "origins": cluster.origins.iter().map(|origin| describe(origin)).collect::<Vec<Value>>(),
+                        "vendored": cluster
+                            .vendored
+                            .iter()
+                            .map(|(copy, is_exact)| {
+                                let mut copy: Value = describe(copy);
+                                copy["match"] = json!(how(*is_exact));
+                                copy
+                            })
+                            .collect::<Vec<Value>>(),
+                    })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&clusters)?);
+        }
+        Format::Csv => {
+            // One row per vendored copy and the package it was likely copied from
+            println!(
+                "{}",
+                csv_row(&[
+                    "package",
+                    "module",
+                    "file",
+                    "origin_package",
+                    "origin_module",
+                    "origin_file",
+                    "match"
+                ])
+            );
+            for cluster in &clusters {
+                for (copy, is_exact) in &cluster.vendored {
+                    for origin in &cluster.origins {
+                        println!(
+                            "{}",
+                            csv_row(&[
+                                &copy.package.to_string(),
+                                &copy.name,
+                                &copy.file,
+                                &origin.package.to_string(),
+                                &origin.name,
+                                &origin.file,
+                                how(*is_exact),
+                            ])
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_text(clusters: &[Cluster]) {
+    let (mut vendored, mut republished) = (0, 0);
+    for cluster in clusters {
+        let origin_names: String = cluster
+            .origins
+            .iter()
+            .map(|origin| format!("{} {}", origin.package, origin.name))
+            .collect::<Vec<String>>()
+            .join(", ");
+        if cluster.origins.len() > 1 {
+            republished += 1;
+            println!(
+                "{} {origin_names}",
+                "Published by several packages:".yellow()
+            );
+        }
+
+        for (copy, is_exact) in &cluster.vendored {
+            vendored += 1;
+            println!(
+                "{} {} vendors {} ({})",
+                copy.package.to_string().blue(),
+                copy.name,
+                origin_names,
+                how(*is_exact)
+            );
+        }
+    }
+
+    println!(
+        "{}",
+        format!(
+            "Found {vendored} vendored modules and {republished} modules exposed by more than one package"
+        )
+        .green()
+    );
+}
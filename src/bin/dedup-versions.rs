@@ -0,0 +1,257 @@
+use colored::*;
+use elm_dedup_project::{
+    corpus::{self, version_key},
+    dedup::{self, hash_tree, Backend, Content, BACKENDS},
+    error::Error,
+    package::PackageId,
+    report::{self, csv_row, Format, FORMATS},
+};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+type Tree = BTreeMap<PathBuf, Content>;
+
+/// How much of a version was already there, unchanged at the same path, in the previous one
+#[derive(Serialize)]
+struct Step {
+    from: String,
+    to: String,
+    identical_files: usize,
+    files: usize,
+    identical_modules: usize,
+    modules: usize,
+}
+
+#[derive(Serialize)]
+struct PackageReport {
+    steps: Vec<Step>,
+    versions: usize,
+    /// Sum of the sizes of all versions
+    total_bytes: u64,
+    /// Size of the distinct file contents across all versions
+    unique_bytes: u64,
+}
+
+fn is_module(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "elm")
+}
+
+fn compare(from: &Tree, to: &Tree) -> (usize, usize, usize, usize) {
+    let (mut identical_files, mut identical_modules, mut modules) = (0, 0, 0);
+    for (path, content) in to {
+        let identical: bool = from.get(path) == Some(content);
+        if identical {
+            identical_files += 1;
+        }
+        if is_module(path) {
+            modules += 1;
+            if identical {
+                identical_modules += 1;
+            }
+        }
+    }
+    (identical_files, to.len(), identical_modules, modules)
+}
+
+fn report_package(
+    mut versions: Vec<PackageId>,
+    backend: &dyn Backend,
+) -> Result<PackageReport, Error> {
+    versions.sort_by_key(|id| version_key(&id.version));
+
+    let mut trees: Vec<(String, Tree)> = Vec::new();
+    for id in versions {
+        let tree: Tree = hash_tree(Path::new(&id.path()), backend)?;
+        trees.push((id.version, tree));
+    }
+
+    let steps: Vec<Step> = trees
+        .windows(2)
+        .map(|pair| {
+            let [(from, from_tree), (to, to_tree)] = pair else {
+                unreachable!("windows(2) always yields pairs");
+            };
+            let (identical_files, files, identical_modules, modules) = compare(from_tree, to_tree);
+            Step {
+                from: from.clone(),
+                to: to.clone(),
+                identical_files,
+                files,
+                identical_modules,
+                modules,
+            }
+        })
+        .collect();
+
+    let contents: Vec<&Content> = trees.iter().flat_map(|(_, tree)| tree.values()).collect();
+    let total_bytes: u64 = contents.iter().map(|content| content.size).sum();
+    let unique_bytes: u64 = contents
+        .into_iter()
+        .collect::<BTreeSet<&Content>>()
+        .into_iter()
+        .map(|content| content.size)
+        .sum();
+
+    Ok(PackageReport {
+        steps,
+        versions: trees.len(),
+        total_bytes,
+        unique_bytes,
+    })
+}
+
+fn percentage(part: usize, whole: usize) -> String {
+    if whole == 0 {
+        return "-".to_string();
+    }
+    format!("{:.0}%", part as f64 * 100.0 / whole as f64)
+}
+
+fn human_bytes(bytes: u64) -> String {
+    let units: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value: f64 = bytes as f64;
+    let mut unit: usize = 0;
+    while value >= 1024.0 && unit + 1 < units.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", units[unit])
+}
+
+fn main() -> Result<(), Error> {
+    let mut show_steps: bool = false;
+    let mut format: Format = Format::Text;
+    let mut backend: Box<dyn Backend> = Box::new(dedup::Exact);
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--steps" => show_steps = true,
+            "--hash" => {
+                backend = args
+                    .next()
+                    .and_then(|name| dedup::backend(&name))
+                    .ok_or(format!("--hash needs one of {}", BACKENDS.join(", ")))?
+            }
+            "--format" => {
+                format = args
+                    .next()
+                    .and_then(|name| report::format(&name))
+                    .ok_or(format!("--format needs one of {}", FORMATS.join(", ")))?
+            }
+            _ => return Err(format!("Unknown argument: {arg}").into()),
+        }
+    }
+
+    let mut packages: BTreeMap<String, Vec<PackageId>> = BTreeMap::new();
+    for id in corpus::package_versions()? {
+        packages.entry(id.package_name()).or_default().push(id);
+    }
+
+    eprintln!("{}", "Hashing package versions".blue());
+    let reports: Vec<(String, PackageReport)> = packages
+        .into_par_iter()
+        .map(|(package_name, versions)| {
+            let report: PackageReport = report_package(versions, backend.as_ref())?;
+            Ok((package_name, report))
+        })
+        .collect::<Result<_, Error>>()?;
+
+    match format {
+        Format::Text => {}
+        Format::Json => {
+            let packages: BTreeMap<&String, &PackageReport> = reports
+                .iter()
+                .map(|(package_name, report)| (package_name, report))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&packages)?);
+            return Ok(());
+        }
+        Format::Csv => {
+            // One row per version step
+            println!(
+                "{}",
+                csv_row(&[
+                    "package",
+                    "from",
+                    "to",
+                    "identical_files",
+                    "files",
+                    "identical_modules",
+                    "modules"
+                ])
+            );
+            for (package_name, report) in &reports {
+                for step in &report.steps {
+                    println!(
+                        "{}",
+                        csv_row(&[
+                            package_name,
+                            &step.from,
+                            &step.to,
+                            &step.identical_files.to_string(),
+                            &step.files.to_string(),
+                            &step.identical_modules.to_string(),
+                            &step.modules.to_string(),
+                        ])
+                    );
+                }
+            }
+            return Ok(());
+        }
+    }
+
+    let (mut total_bytes, mut unique_bytes) = (0, 0);
+    let (mut identical_files, mut files, mut identical_modules, mut modules) = (0, 0, 0, 0);
+    for (package_name, report) in &reports {
+        total_bytes += report.total_bytes;
+        unique_bytes += report.unique_bytes;
+        if report.versions < 2 {
+            continue;
+        }
+
+        println!(
+            "{}: {} versions, {} total, {} unique",
+            package_name.blue(),
+            report.versions,
+            human_bytes(report.total_bytes),
+            human_bytes(report.unique_bytes)
+        );
+        for step in &report.steps {
+            identical_files += step.identical_files;
+            files += step.files;
+            identical_modules += step.identical_modules;
+            modules += step.modules;
+            if show_steps {
+                println!(
+                    "  {} -> {}: {} of files identical ({}/{}), {} of modules ({}/{})",
+                    step.from,
+                    step.to,
+                    percentage(step.identical_files, step.files),
+                    step.identical_files,
+                    step.files,
+                    percentage(step.identical_modules, step.modules),
+                    step.identical_modules,
+                    step.modules
+                );
+            }
+        }
+    }
+
+    println!(
+        "{}",
+        format!(
+            "Across version steps {} of files and {} of modules are unchanged. Deduplicating versions would shrink the corpus from {} to {}",
+            percentage(identical_files, files),
+            percentage(identical_modules, modules),
+            human_bytes(total_bytes),
+            human_bytes(unique_bytes)
+        )
+        .green()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,292 @@
+use colored::*;
+use elm_dedup_project::{
+    corpus::{self, subdirectories, CORPUS_VAR},
+    error::Error,
+    lock,
+    manifest::{self, Manifest},
+    package::{is_version, PackageId},
+};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Below this much free space, a full download or an elm-review run over the corpus is likely to fail
+const MIN_FREE_BYTES: u64 = 5 * 1024 * 1024 * 1024;
+
+/// How many offending paths to show for each problem
+const EXAMPLES: usize = 5;
+
+/// The programs the tools shell out to, and which tools need them
+const TOOLS: [(&str, &str); 11] = [
+    ("unzip", "download-repos"),
+    ("sha1sum", "download-repos"),
+    ("git", "download-repos --git"),
+    ("elm", "run-elm-review"),
+    ("elm-review", "run-elm-review"),
+    ("node", "run-elm-review"),
+    ("npx", "run-elm-review"),
+    ("tar", "snapshot"),
+    ("gzip", "snapshot"),
+    ("sha256sum", "snapshot"),
+    ("df", "doctor"),
+];
+
+struct Report {
+    problems: usize,
+}
+
+impl Report {
+    fn check(&self, name: &str) {
+        println!("{}", name.blue());
+    }
+
+    fn ok(&self, detail: impl AsRef<str>) {
+        println!("  {} {}", "ok".green(), detail.as_ref());
+    }
+
+    /// Reports a problem found in `items`, if there are any, with the way to fix it
+    fn problem(&mut self, what: &str, items: &[String], fix: &str) {
+        if items.is_empty() {
+            return;
+        }
+
+        self.problems += 1;
+        println!("  {} {what} ({})", "!!".yellow(), items.len());
+        for item in items.iter().take(EXAMPLES) {
+            println!("     {item}");
+        }
+        if items.len() > EXAMPLES {
+            println!("     and {} more", items.len() - EXAMPLES);
+        }
+        println!("     {} {fix}", "fix:".dimmed());
+    }
+}
+
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+/// Free space on the filesystem holding `dir`, from `df`
+fn free_bytes(dir: &str) -> Result<u64, Error> {
+    let output = Command::new("df").args(["-Pk", dir]).output()?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr)
+            .trim()
+            .to_string()
+            .into());
+    }
+
+    // The second line is `filesystem blocks used available capacity mountpoint`, in KiB
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .nth(1)
+        .and_then(|line| line.split_whitespace().nth(3))
+        .and_then(|available| available.parse::<u64>().ok())
+        .map(|available| available * 1024)
+        .ok_or("Unexpected df output".to_string().into())
+}
+
+/// Whether the git metadata of a checkout points somewhere valid. Checkouts without any, like those restored from
+/// a snapshot, are fine
+fn has_broken_git(dir: &Path) -> bool {
+    let git: PathBuf = dir.join(".git");
+    if git.is_dir() {
+        return !git.join("HEAD").exists();
+    }
+    if !git.is_file() {
+        return false;
+    }
+
+    // Worktrees have a `gitdir: ...` file pointing into their bare repo
+    match fs::read_to_string(&git) {
+        Ok(content) => match content.trim().strip_prefix("gitdir: ") {
+            Some(git_dir) => !dir.join(git_dir).exists(),
+            None => true,
+        },
+        Err(_) => true,
+    }
+}
+
+fn main() -> Result<(), Error> {
+    if let Some(arg) = std::env::args().nth(1) {
+        return Err(format!("Unknown argument: {arg}").into());
+    }
+
+    let mut report: Report = Report { problems: 0 };
+
+    report.check("Tools");
+    let missing_tools: Vec<String> = TOOLS
+        .iter()
+        .filter(|(program, _)| !on_path(program))
+        .map(|(program, tool)| format!("{program}, needed by {tool}"))
+        .collect();
+    report.problem("not on PATH", &missing_tools, "install them");
+    if missing_tools.is_empty() {
+        report.ok(format!("{} tools are on PATH", TOOLS.len()));
+    }
+
+    report.check("Workspace lock");
+    match lock::holder() {
+        Some(holder) => report.problem(
+            "locked",
+            &[holder],
+            "wait for that run to finish, or pass --force to the next command if it's gone",
+        ),
+        None => report.ok("not locked"),
+    }
+
+    let root: &str = corpus::root();
+    if !Path::new(root).is_dir() {
+        report.check("Corpus");
+        report.problem(
+            "no corpus directory",
+            &[root.to_string()],
+            &format!("run download-repos or snapshot restore, or point {CORPUS_VAR} at the corpus if it's mounted elsewhere"),
+        );
+        return finish(report);
+    }
+
+    report.check("Disk space");
+    let free: u64 = free_bytes(root)?;
+    if free < MIN_FREE_BYTES {
+        report.problem(
+            "low on disk space",
+            &[format!("{} MiB free", free / 1024 / 1024)],
+            &format!("free some space, e.g. by deleting elm-stuff directories under {root}, or move the corpus to a bigger drive and set {CORPUS_VAR}"),
+        );
+    } else {
+        report.ok(format!("{} GiB free", free / 1024 / 1024 / 1024));
+    }
+
+    report.check("Corpus layout");
+    let mut old_layout: Vec<String> = Vec::new();
+    for author in subdirectories(root)? {
+        for name in subdirectories(&format!("{root}/{author}"))? {
+            if Path::new(&format!("{root}/{author}/{name}/elm.json")).exists() {
+                old_layout.push(format!("{root}/{author}/{name}"));
+            }
+        }
+    }
+    report.problem(
+        "checkouts in the old {author}/{name} layout, without a version directory",
+        &old_layout,
+        "run migrate-layout",
+    );
+    let old_directories: Vec<String> = corpus::OLD_DIRECTORIES
+        .iter()
+        .filter(|dir| Path::new(dir).is_dir())
+        .map(|dir| dir.to_string())
+        .collect();
+    report.problem(
+        "bare repos, docs or apps outside the corpus, where they were kept before",
+        &old_directories,
+        &format!(
+            "move them to {}, {} and {}, then run git worktree repair in each bare repo",
+            corpus::bare_root(),
+            corpus::docs_root(),
+            corpus::apps_root()
+        ),
+    );
+
+    // The subdirectories of an old layout checkout aren't versions
+    let versions: Vec<PackageId> = corpus::package_versions()?
+        .into_iter()
+        .filter(|id| !old_layout.contains(&format!("{root}/{}/{}", id.author, id.name)))
+        .collect();
+    let (mut not_versions, mut without_elm_json, mut broken_git): (Vec<_>, Vec<_>, Vec<_>) =
+        (Vec::new(), Vec::new(), Vec::new());
+    let mut on_disk: BTreeSet<String> = BTreeSet::new();
+    for id in &versions {
+        let path: String = id.path();
+        if !is_version(&id.version) {
+            not_versions.push(path);
+            continue;
+        }
+        on_disk.insert(id.to_string());
+        if !Path::new(&path).join("elm.json").exists() {
+            without_elm_json.push(path.clone());
+        }
+        if has_broken_git(Path::new(&path)) {
+            broken_git.push(path);
+        }
+    }
+    report.problem(
+        "directories that aren't versions",
+        &not_versions,
+        &format!(
+            "move them out of {root}, or drop a {} file in them",
+            corpus::IGNORE_FILE
+        ),
+    );
+    if old_layout.is_empty() && old_directories.is_empty() && not_versions.is_empty() {
+        report.ok(format!("{} package versions", on_disk.len()));
+    }
+
+    report.check("Checkouts");
+    report.problem(
+        "checkouts without an elm.json",
+        &without_elm_json,
+        "delete them and run download-repos again",
+    );
+    report.problem(
+        "checkouts with broken git metadata",
+        &broken_git,
+        "delete them, run git worktree prune in their bare repo if you use --worktrees, and run download-repos again",
+    );
+    if without_elm_json.is_empty() && broken_git.is_empty() {
+        report.ok("every checkout has an elm.json and valid git metadata");
+    }
+
+    report.check("Manifest");
+    let manifest: Manifest = Manifest::load()?;
+    let recorded: BTreeSet<String> = manifest
+        .packages
+        .iter()
+        .flat_map(|(package_name, versions)| {
+            versions
+                .iter()
+                .map(move |version| format!("{package_name}@{version}"))
+        })
+        .collect();
+    let unrecorded: Vec<String> = on_disk.difference(&recorded).cloned().collect();
+    // Ignored checkouts stay in the manifest, but corpus walks don't list them
+    let missing: Vec<String> = recorded
+        .difference(&on_disk)
+        .filter(|release| {
+            release
+                .parse::<PackageId>()
+                .map_or(true, |id| !corpus::ignores(&id))
+        })
+        .cloned()
+        .collect();
+    report.problem(
+        &format!("checkouts missing from {}", manifest::manifest_path()),
+        &unrecorded,
+        "run migrate-layout, which records the checkouts already in place",
+    );
+    report.problem(
+        &format!(
+            "releases in {} that aren't checked out",
+            manifest::manifest_path()
+        ),
+        &missing,
+        "put them in a file and run download-repos --list with it, or remove them from the manifest",
+    );
+    if unrecorded.is_empty() && missing.is_empty() {
+        report.ok(format!("matches the {} checkouts", on_disk.len()));
+    }
+
+    finish(report)
+}
+
+fn finish(report: Report) -> Result<(), Error> {
+    if report.problems > 0 {
+        return Err(format!("Found {} problems", report.problems).into());
+    }
+    println!("{}", "No problems found".green());
+    Ok(())
+}
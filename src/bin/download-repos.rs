@@ -1,109 +1,982 @@
 use colored::*;
+use elm_dedup_project::{
+    corpus::{self, version_key},
+    error::Error,
+    lock::WorkspaceLock,
+    manifest::{self, Manifest, Upstream},
+    package::{is_name_part, PackageId},
+};
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 use serde::Deserialize;
-use std::{fs, io, path::Path, process::Command};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt::Display,
+    fs,
+    io::{self, Write},
+    path::Path,
+    process::Command,
+    sync::{Condvar, Mutex},
+};
+use tokio::runtime::Handle;
 
-#[derive(Debug)]
-enum Error {
-    Reqwest(reqwest::Error),
-    IO(io::Error),
-    Other(String),
+/// An entry of the registry's `search.json`
+#[derive(Deserialize)]
+struct SearchEntry {
+    name: String,
+    version: String,
+}
+
+/// The registry's `endpoint.json` for a release: where its zipball is, and the SHA-1 of the zip
+#[derive(Deserialize)]
+struct Endpoint {
+    url: String,
+    hash: String,
 }
 
-impl From<io::Error> for Error {
-    fn from(e: io::Error) -> Self {
-        Error::IO(e)
+/// The part of GitHub's repository API response we need
+#[derive(Deserialize)]
+struct GitHubRepo {
+    full_name: String,
+}
+
+enum CloneStatus {
+    Cloned,
+    AlreadyPresent,
+    Error,
+    /// git or GitHub says the repository doesn't exist
+    NotFound,
+    /// The repository was deleted or renamed, see the manifest
+    Gone,
+}
+
+/// Caps how many clones run at once against each author, as GitHub throttles many parallel clones from one owner
+struct AuthorLimit {
+    max: usize,
+    running: Mutex<HashMap<String, usize>>,
+    freed: Condvar,
+}
+
+/// A clone slot for an author, given back on drop
+struct AuthorSlot<'a> {
+    limit: &'a AuthorLimit,
+    author: String,
+}
+
+impl AuthorLimit {
+    fn new(max: usize) -> AuthorLimit {
+        AuthorLimit {
+            max,
+            running: Mutex::new(HashMap::new()),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// Waits until fewer than `max` clones of `author` are running
+    fn acquire(&self, author: &str) -> AuthorSlot<'_> {
+        let mut running = self.running.lock().unwrap();
+        while running.get(author).copied().unwrap_or(0) >= self.max {
+            running = self.freed.wait(running).unwrap();
+        }
+        *running.entry(author.to_string()).or_default() += 1;
+        AuthorSlot {
+            limit: self,
+            author: author.to_string(),
+        }
     }
 }
 
-impl From<reqwest::Error> for Error {
-    fn from(e: reqwest::Error) -> Self {
-        Error::Reqwest(e)
+impl Drop for AuthorSlot<'_> {
+    fn drop(&mut self) {
+        let mut running = self.limit.running.lock().unwrap();
+        if let Some(count) = running.get_mut(&self.author) {
+            *count -= 1;
+        }
+        self.limit.freed.notify_all();
     }
 }
 
-impl From<String> for Error {
-    fn from(e: String) -> Self {
-        Error::Other(e)
+/// Orders packages taking one from each author in turn, so the packages running at the same time are mostly from
+/// different authors and rarely wait on their `AuthorLimit`
+fn interleave_authors(
+    by_package: BTreeMap<String, Vec<PackageId>>,
+) -> Vec<(String, Vec<PackageId>)> {
+    let mut by_author: BTreeMap<String, VecDeque<(String, Vec<PackageId>)>> = BTreeMap::new();
+    for (package_name, versions) in by_package {
+        let author: String = package_name
+            .split_once('/')
+            .map(|(author, _)| author.to_string())
+            .unwrap_or_default();
+        by_author
+            .entry(author)
+            .or_default()
+            .push_back((package_name, versions));
     }
+
+    let mut result: Vec<(String, Vec<PackageId>)> = Vec::new();
+    while !by_author.is_empty() {
+        by_author.retain(|_, packages| {
+            if let Some(package) = packages.pop_front() {
+                result.push(package);
+            }
+            !packages.is_empty()
+        });
+    }
+    result
 }
 
-#[derive(Deserialize)]
-struct Package {
-    name: String,
-    version: String,
+/// The outcome of cloning the versions of one package
+struct PackageClones {
+    package_name: String,
+    /// What was found out about the package's repository, to record in the manifest
+    upstream: Option<Upstream>,
+    statuses: Vec<(PackageId, CloneStatus)>,
 }
 
-enum CloneStatus {
-    Cloned,
-    AlreadyPresent,
-    Error,
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+/// Writes whole lines to stdout under a lock, so output from parallel clones never interleaves mid-line
+struct Logger {
+    verbosity: Verbosity,
+}
+
+impl Logger {
+    fn log(&self, level: Verbosity, lines: impl Display) {
+        if level > self.verbosity {
+            return;
+        }
+
+        let mut stdout = io::stdout().lock();
+        // A closed pipe shouldn't abort the clones that are still running
+        let _ = writeln!(stdout, "{lines}");
+        let _ = stdout.flush();
+    }
+
+    fn info(&self, line: impl Display) {
+        self.log(Verbosity::Normal, line)
+    }
+
+    fn verbose(&self, line: impl Display) {
+        self.log(Verbosity::Verbose, line)
+    }
+
+    fn error(&self, line: impl Display) {
+        self.log(Verbosity::Quiet, line)
+    }
+}
+
+struct Options {
+    verbosity: Verbosity,
+    force: bool,
+    /// Clone releases with git over SSH, instead of downloading their zipball over HTTPS
+    git: bool,
+    /// Keep one bare repo per package under `corpus::bare_root` and check versions out as worktrees of it
+    worktrees: bool,
+    /// Base URL of the package registry, without the trailing slash
+    registry: String,
+    /// Only fetch the releases published since the last `--since` sync
+    since: bool,
+    /// Only fetch the `author/name@version` releases listed in this file
+    list: Option<String>,
+    /// Clone the `owner/repo[@ref]` Elm applications listed in this file into `corpus::apps_root`, instead of packages
+    apps: Option<String>,
+    /// Also mirror each version's published docs.json and README.md under `corpus::docs_root`
+    docs: bool,
+    /// Clone packages whose repository was renamed from its new name, instead of skipping them
+    follow_renames: bool,
+    /// How many packages of the same author are cloned at once
+    per_author: usize,
+}
+
+fn parse_options() -> Result<Options, Error> {
+    let mut options = Options {
+        verbosity: Verbosity::Normal,
+        force: false,
+        git: false,
+        worktrees: false,
+        registry: "https://package.elm-lang.org".to_string(),
+        since: false,
+        list: None,
+        apps: None,
+        docs: false,
+        follow_renames: false,
+        per_author: 2,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-q" | "--quiet" => options.verbosity = Verbosity::Quiet,
+            "-v" | "--verbose" => options.verbosity = Verbosity::Verbose,
+            "--force" => options.force = true,
+            "--git" => options.git = true,
+            // Worktrees are checked out from a bare repo, so they need git
+            "--worktrees" => {
+                options.worktrees = true;
+                options.git = true;
+            }
+            "--since" => options.since = true,
+            "--list" => options.list = Some(args.next().ok_or("--list needs a file".to_string())?),
+            "--apps" => options.apps = Some(args.next().ok_or("--apps needs a file".to_string())?),
+            "--docs" => options.docs = true,
+            "--follow-renames" => options.follow_renames = true,
+            "--per-author" => {
+                options.per_author = args
+                    .next()
+                    .and_then(|count| count.parse().ok())
+                    .filter(|count| *count > 0)
+                    .ok_or("--per-author needs a positive number".to_string())?
+            }
+            "--registry" => {
+                let registry: String = args.next().ok_or("--registry needs a URL".to_string())?;
+                options.registry = registry.trim_end_matches('/').to_string();
+            }
+            _ => return Err(format!("Unknown argument: {arg}").into()),
+        }
+    }
+    if options.since && options.list.is_some() {
+        return Err("--since and --list can't be used together"
+            .to_string()
+            .into());
+    }
+    Ok(options)
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
-    println!("{}", "Getting packages list".blue());
-    let packages: Vec<Package> = reqwest::get("https://package.elm-lang.org/search.json")
-        .await?
-        .json()
-        .await?;
+    let options: Options = parse_options()?;
+    let logger = Logger {
+        verbosity: options.verbosity,
+    };
+    let _lock: WorkspaceLock = WorkspaceLock::acquire(options.force)?;
+
+    if let Some(apps) = &options.apps {
+        return clone_apps(&logger, &options, apps);
+    }
 
-    let result: Vec<CloneStatus> = packages
-        .into_par_iter()
-        .map(|package: Package| {
-            let package_name: String = package.name;
-            let package_version: String = package.version;
+    let mut manifest: Manifest = Manifest::load()?;
 
-            if Path::new(&format!("repos/{package_name}/{package_version}")).exists() {
-                return Ok(CloneStatus::AlreadyPresent);
+    let (packages, high_water_mark): (Vec<PackageId>, Option<usize>) =
+        match (options.since, manifest.since, &options.list) {
+            (false, _, Some(list)) => (corpus::read_package_list(list)?, None),
+            (false, _, None) => {
+                logger.info(format!(
+                    "{} {}",
+                    "Getting packages list from".blue(),
+                    options.registry
+                ));
+                (fetch_packages(&options.registry).await?, None)
             }
+            (true, Some(known), _) => {
+                let new: Vec<PackageId> = fetch_since(&options.registry, known).await?;
+                report_changes(&logger, &manifest, &new);
+                let high_water_mark: usize = known + new.len();
+                (new, Some(high_water_mark))
+            }
+            (true, None, _) => {
+                // First incremental sync: do a full one, and count every release published so far as seen
+                let known: usize = fetch_since(&options.registry, 0).await?.len();
+                (fetch_packages(&options.registry).await?, Some(known))
+            }
+        };
 
-            println!(
-                "{} {}@{}",
-                "Cloning".green(),
-                package_name.blue(),
-                package_version.blue()
-            );
-
-            fs::create_dir_all(format!("repos/{package_name}"))?;
-
-            // Use git URL to avoid username/password prompts
-            let url: String = format!("git@github.com:{package_name}.git");
-            let is_ok: bool = Command::new("git")
-                .args([
-                    "clone",
-                    "--quiet",
-                    "--branch",
-                    &package_version,
-                    "--depth",
-                    "1",
-                    &url,
-                    &format!("repos/{package_name}/{package_version}"),
-                ])
-                .spawn()?
-                .wait()?
-                .success();
-            if !is_ok {
-                println!("{} {}", "!!! Error cloning ".red(), package_name.blue());
-
-                return Ok(CloneStatus::Error);
+    // Versions of the same package are cloned one after the other, as with --worktrees they share a bare repo
+    let mut by_package: BTreeMap<String, Vec<PackageId>> = BTreeMap::new();
+    let mut skipped: usize = 0;
+    for package in packages {
+        match manifest.gone.get(&package.package_name()) {
+            Some(Upstream::MovedTo(_)) if options.follow_renames => {}
+            Some(_) => {
+                skipped += 1;
+                continue;
             }
+            None => {}
+        }
+        by_package
+            .entry(package.package_name())
+            .or_default()
+            .push(package);
+    }
+    if skipped > 0 {
+        logger.info(format!(
+            "{} {skipped} releases of packages gone upstream, see gone in {}",
+            "Skipping".yellow(),
+            manifest::manifest_path()
+        ));
+    }
+
+    let runtime: Handle = Handle::current();
+    let client: reqwest::Client = reqwest::Client::new();
 
-            Ok(CloneStatus::Cloned)
+    let limit: AuthorLimit = AuthorLimit::new(options.per_author);
+
+    // Rayon may run part of the loop on this thread, which drives the runtime: let it block there
+    let result: Vec<PackageClones> = tokio::task::block_in_place(|| {
+        interleave_authors(by_package)
+            .into_par_iter()
+            .map(|(package_name, versions): (String, Vec<PackageId>)| {
+                let _slot: AuthorSlot =
+                    limit.acquire(versions.first().map_or("", |package| &package.author));
+                let mut repo: String = match manifest.gone.get(&package_name) {
+                    Some(Upstream::MovedTo(to)) => to.clone(),
+                    _ => package_name.clone(),
+                };
+                // Deleted, or renamed without --follow-renames: the remaining versions are gone too
+                let mut gone: Option<Upstream> = None;
+                let mut moved: Option<Upstream> = None;
+                let mut statuses: Vec<(PackageId, CloneStatus)> = Vec::new();
+                for package in versions {
+                    if gone.is_some() {
+                        statuses.push((package, CloneStatus::Gone));
+                        continue;
+                    }
+
+                    let mut status: CloneStatus =
+                        clone(&logger, &options, &runtime, &client, &package, &repo)?;
+                    if let CloneStatus::NotFound = status {
+                        status = match look_up(&logger, &runtime, &client, &repo) {
+                            Some(Upstream::MovedTo(to)) if options.follow_renames => {
+                                logger.info(format!(
+                                    "{} {} {} {}",
+                                    "Following".green(),
+                                    repo.blue(),
+                                    "to".green(),
+                                    to.blue()
+                                ));
+                                repo = to.clone();
+                                moved = Some(Upstream::MovedTo(to));
+                                clone(&logger, &options, &runtime, &client, &package, &repo)?
+                            }
+                            Some(upstream) => {
+                                logger.error(format!(
+                                    "{} {} {}",
+                                    "!!! Gone upstream".red(),
+                                    package_name.blue(),
+                                    describe(&upstream)
+                                ));
+                                gone = Some(upstream);
+                                CloneStatus::Gone
+                            }
+                            None => CloneStatus::Error,
+                        };
+                    }
+
+                    if options.docs
+                        && matches!(status, CloneStatus::Cloned | CloneStatus::AlreadyPresent)
+                    {
+                        mirror_docs(&logger, &options, &runtime, &client, &package)?;
+                    }
+                    statuses.push((package, status));
+                }
+                Ok(PackageClones {
+                    package_name,
+                    upstream: gone.or(moved),
+                    statuses,
+                })
+            })
+            .collect::<Result<_, Error>>()
+    })?;
+
+    for clones in &result {
+        if let Some(upstream) = &clones.upstream {
+            manifest
+                .gone
+                .insert(clones.package_name.clone(), upstream.clone());
+        }
+        for (package, status) in &clones.statuses {
+            if let CloneStatus::Cloned | CloneStatus::AlreadyPresent = status {
+                manifest.insert(package)
+            }
+        }
+    }
+
+    let (present, cloned, error, gone) = summarize(
+        result
+            .iter()
+            .flat_map(|clones| clones.statuses.iter().map(|(_, status)| status)),
+    );
+    logger.error(
+        format!(
+            "Cloned {cloned}, errored {error}, already present {present}, gone upstream {gone}"
+        )
+        .green(),
+    );
+
+    if let Some(high_water_mark) = high_water_mark {
+        if error == 0 {
+            manifest.since = Some(high_water_mark);
+        } else {
+            // Keep the old mark, so the next sync retries the failed releases
+            logger.error("Some clones failed, the next --since sync will retry them".yellow());
+        }
+    }
+    manifest.save()?;
+
+    Ok(())
+}
+
+/// The latest version of every package, from `search.json` or, for registries that don't serve it, from the
+/// `all-packages` list of every release
+async fn fetch_packages(registry: &str) -> Result<Vec<PackageId>, Error> {
+    let response = reqwest::get(format!("{registry}/search.json")).await?;
+    if response.status() != reqwest::StatusCode::NOT_FOUND {
+        let entries: Vec<SearchEntry> = response.error_for_status()?.json().await?;
+        return entries
+            .into_iter()
+            .map(|entry: SearchEntry| {
+                PackageId::new(&entry.name, &entry.version)
+                    .ok_or(format!("Invalid package {}@{}", entry.name, entry.version).into())
+            })
+            .collect();
+    }
+
+    let all_packages: HashMap<String, Vec<String>> =
+        reqwest::get(format!("{registry}/all-packages"))
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+    Ok(all_packages
+        .into_iter()
+        .filter_map(|(name, versions)| {
+            let version: String = versions.into_iter().max_by_key(|v| version_key(v))?;
+            PackageId::new(&name, &version)
         })
-        .collect::<Result<_, Error>>()?;
-
-    let (present, cloned, error) = result
-        .iter()
-        .fold((0, 0, 0), |(present, cloned, error), r| match r {
-            CloneStatus::Cloned => (present, cloned + 1, error),
-            CloneStatus::AlreadyPresent => (present + 1, cloned, error),
-            CloneStatus::Error => (present, cloned, error + 1),
-        });
-    println!(
-        "{}",
-        format!("Cloned {cloned}, errored {error}, already present {present}").green(),
+        .collect())
+}
+
+/// The releases published after the first `known` ones, from a list of `author/name@version` ordered newest first
+async fn fetch_since(registry: &str, known: usize) -> Result<Vec<PackageId>, Error> {
+    let releases: Vec<String> = reqwest::get(format!("{registry}/all-packages/since/{known}"))
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    releases
+        .into_iter()
+        .map(|release: String| Ok(release.parse()?))
+        .collect()
+}
+
+/// Lists the releases an incremental sync found, telling new packages from new versions of known ones
+fn report_changes(logger: &Logger, manifest: &Manifest, new: &[PackageId]) {
+    if new.is_empty() {
+        logger.info("Nothing published since the last sync".green());
+        return;
+    }
+
+    logger.info(format!(
+        "{} {} releases since the last sync",
+        "Found".blue(),
+        new.len()
+    ));
+    for package in new {
+        let latest: Option<&String> = manifest
+            .packages
+            .get(&package.package_name())
+            .and_then(|versions| versions.iter().max_by_key(|v| version_key(v)));
+        match latest {
+            None => logger.info(format!("  new package {}", package.to_string().blue())),
+            Some(latest) => logger.info(format!(
+                "  new version {} (had {latest})",
+                package.to_string().blue()
+            )),
+        }
+    }
+}
+
+/// Applications exercise ports, effects and sizes that packages don't. They're kept apart from the package corpus,
+/// in `{apps_root}/{owner}/{repo}`
+fn clone_apps(logger: &Logger, options: &Options, list: &str) -> Result<(), Error> {
+    let mut apps: Vec<(String, Option<String>)> = Vec::new();
+    for word in corpus::list_words(list)? {
+        let (repo, git_ref): (&str, Option<&str>) = match word.split_once('@') {
+            Some((repo, git_ref)) => (repo, Some(git_ref)),
+            None => (&word, None),
+        };
+        // Anything else wouldn't land in its own `{owner}/{repo}` directory
+        let is_repo: bool = repo
+            .split_once('/')
+            .is_some_and(|(owner, name)| is_name_part(owner) && is_name_part(name));
+        if !is_repo {
+            return Err(format!("{list}: expected owner/repo[@ref], got {word}").into());
+        }
+        apps.push((repo.to_string(), git_ref.map(|git_ref| git_ref.to_string())));
+    }
+
+    let runtime: Handle = Handle::current();
+    let client: reqwest::Client = reqwest::Client::new();
+
+    let result: Vec<CloneStatus> = tokio::task::block_in_place(|| {
+        apps.into_par_iter()
+            .map(|(repo, git_ref): (String, Option<String>)| {
+                let target: String = format!("{}/{repo}", corpus::apps_root());
+                if Path::new(&target).exists() {
+                    logger.verbose(format!("{} {}", "Already present".dimmed(), repo.blue()));
+                    return Ok(CloneStatus::AlreadyPresent);
+                }
+
+                if let Some(parent) = Path::new(&target).parent() {
+                    fs::create_dir_all(parent)?;
+                }
+
+                if !options.git {
+                    logger.info(format!("{} {}", "Downloading".green(), repo.blue()));
+                    // Without a ref, GitHub serves the default branch
+                    let url: String = match &git_ref {
+                        Some(git_ref) => format!("https://github.com/{repo}/zipball/{git_ref}"),
+                        None => format!("https://github.com/{repo}/zipball"),
+                    };
+                    return fetch_zip(logger, &runtime, &client, &repo, &url, None, &target);
+                }
+
+                logger.info(format!("{} {}", "Cloning".green(), repo.blue()));
+
+                // Use git URL to avoid username/password prompts
+                let url: String = format!("git@github.com:{repo}.git");
+                let mut args: Vec<&str> = vec!["clone", "--quiet", "--depth", "1"];
+                if let Some(git_ref) = &git_ref {
+                    args.extend(["--branch", git_ref]);
+                }
+                args.extend([url.as_str(), target.as_str()]);
+
+                Ok(match git(logger, &repo, &args)? {
+                    Ok(()) => CloneStatus::Cloned,
+                    Err(_) => CloneStatus::Error,
+                })
+            })
+            .collect::<Result<_, Error>>()
+    })?;
+
+    let (present, cloned, error, _) = summarize(result.iter());
+    logger
+        .error(format!("Cloned {cloned} apps, errored {error}, already present {present}").green());
+
+    Ok(())
+}
+
+/// Counts the already present, cloned, errored and gone clones
+fn summarize<'a>(statuses: impl Iterator<Item = &'a CloneStatus>) -> (usize, usize, usize, usize) {
+    statuses.fold(
+        (0, 0, 0, 0),
+        |(present, cloned, error, gone), status| match status {
+            CloneStatus::Cloned => (present, cloned + 1, error, gone),
+            CloneStatus::AlreadyPresent => (present + 1, cloned, error, gone),
+            CloneStatus::Error | CloneStatus::NotFound => (present, cloned, error + 1, gone),
+            CloneStatus::Gone => (present, cloned, error, gone + 1),
+        },
+    )
+}
+
+/// Clones or downloads a release from the `repo` GitHub repository, usually named like the package
+fn clone(
+    logger: &Logger,
+    options: &Options,
+    runtime: &Handle,
+    client: &reqwest::Client,
+    package: &PackageId,
+    repo: &str,
+) -> Result<CloneStatus, Error> {
+    let package_name: String = package.package_name();
+    let package_version: &str = &package.version;
+    let target: String = package.path();
+    let id: String = package.to_string();
+
+    if Path::new(&target).exists() {
+        logger.verbose(format!("{} {}", "Already present".dimmed(), id.blue()));
+        return Ok(CloneStatus::AlreadyPresent);
+    }
+
+    let action: &str = if options.git {
+        "Cloning"
+    } else {
+        "Downloading"
+    };
+    if repo == package_name {
+        logger.info(format!("{} {}", action.green(), id.blue()));
+    } else {
+        logger.info(format!(
+            "{} {} {} {}",
+            action.green(),
+            id.blue(),
+            "from".green(),
+            repo.blue()
+        ));
+    }
+
+    if let Some(parent) = Path::new(&target).parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    if !options.git {
+        return download(logger, options, runtime, client, package, repo);
+    }
+
+    // Use git URL to avoid username/password prompts
+    let url: String = format!("git@github.com:{repo}.git");
+
+    // Versions of the same package share the objects of a single bare repo
+    let bare: String = format!("{}/{package_name}.git", corpus::bare_root());
+    let git_dir: String = format!("--git-dir={bare}");
+
+    let mut commands: Vec<Vec<&str>> = Vec::new();
+    if !options.worktrees {
+        commands.push(vec![
+            "clone",
+            "--quiet",
+            "--branch",
+            package_version,
+            "--depth",
+            "1",
+            &url,
+            &target,
+        ]);
+    } else {
+        if !Path::new(&bare).exists() {
+            commands.push(vec!["init", "--quiet", "--bare", &bare]);
+            commands.push(vec![&git_dir, "remote", "add", "origin", &url]);
+        } else {
+            // The repository may have been renamed since the bare repo was created, even earlier in this run
+            commands.push(vec![&git_dir, "remote", "set-url", "origin", &url]);
+        }
+        commands.push(vec![
+            &git_dir,
+            "fetch",
+            "--quiet",
+            "--depth",
+            "1",
+            "origin",
+            "tag",
+            package_version,
+        ]);
+        commands.push(vec![
+            &git_dir,
+            "worktree",
+            "add",
+            "--quiet",
+            "--detach",
+            &target,
+            package_version,
+        ]);
+    }
+
+    for args in &commands {
+        if let Err(stderr) = git(logger, &id, args)? {
+            return Ok(if stderr.contains("Repository not found") {
+                CloneStatus::NotFound
+            } else {
+                CloneStatus::Error
+            });
+        }
+    }
+    Ok(CloneStatus::Cloned)
+}
+
+/// Downloads and unpacks the zipball of a release. The registry's endpoint gives its URL and hash, except for
+/// renamed repositories, which are fetched from GitHub unchecked as the registry only knows the old name
+fn download(
+    logger: &Logger,
+    options: &Options,
+    runtime: &Handle,
+    client: &reqwest::Client,
+    package: &PackageId,
+    repo: &str,
+) -> Result<CloneStatus, Error> {
+    let id: String = package.to_string();
+    let fail = |reason: String| {
+        logger.error(format!(
+            "{} {}\n{reason}",
+            "!!! Error downloading".red(),
+            id.blue()
+        ));
+        Ok(CloneStatus::Error)
+    };
+
+    let (url, hash): (String, Option<String>) = if repo == package.package_name() {
+        let endpoint_url: String = format!(
+            "{}/packages/{}/{}/endpoint.json",
+            options.registry,
+            package.package_name(),
+            package.version
+        );
+        let endpoint = match runtime.block_on(async {
+            client
+                .get(&endpoint_url)
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<Endpoint>()
+                .await
+        }) {
+            Ok(endpoint) => endpoint,
+            Err(e) => return fail(e.to_string()),
+        };
+        (endpoint.url, Some(endpoint.hash))
+    } else {
+        (
+            format!("https://github.com/{repo}/zipball/{}", package.version),
+            None,
+        )
+    };
+
+    fetch_zip(
+        logger,
+        runtime,
+        client,
+        &package.to_string(),
+        &url,
+        hash.as_deref(),
+        &package.path(),
+    )
+}
+
+/// Downloads a zipball and unpacks it to `target`, checking it against `hash` if there is one
+fn fetch_zip(
+    logger: &Logger,
+    runtime: &Handle,
+    client: &reqwest::Client,
+    what: &str,
+    url: &str,
+    hash: Option<&str>,
+    target: &str,
+) -> Result<CloneStatus, Error> {
+    let fail = |reason: String| {
+        logger.error(format!(
+            "{} {}\n{reason}",
+            "!!! Error downloading".red(),
+            what.blue()
+        ));
+        Ok(CloneStatus::Error)
+    };
+
+    let response = match runtime.block_on(client.get(url).send()) {
+        Ok(response) => response,
+        Err(e) => return fail(e.to_string()),
+    };
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        logger.error(format!(
+            "{} {}\n{url} not found",
+            "!!! Error downloading".red(),
+            what.blue()
+        ));
+        return Ok(CloneStatus::NotFound);
+    }
+    if !response.status().is_success() {
+        return fail(format!("{url} ({})", response.status()));
+    }
+    let bytes = match runtime.block_on(response.bytes()) {
+        Ok(bytes) => bytes,
+        Err(e) => return fail(e.to_string()),
+    };
+
+    // Hidden, so a download cut short isn't taken for a version by corpus walks
+    let (parent, name) = target
+        .rsplit_once('/')
+        .ok_or(format!("Can't download to {target}"))?;
+    let zip: String = format!("{parent}/.{name}.zip");
+    let unpacked: String = format!("{parent}/.{name}.download");
+    if Path::new(&unpacked).exists() {
+        fs::remove_dir_all(&unpacked)?;
+    }
+    fs::write(&zip, &bytes)?;
+
+    // Clean up before any error is returned, so it doesn't leave the zip behind
+    let result: Result<Result<(), String>, Error> = unpack(&zip, &unpacked, target, hash, url);
+    fs::remove_file(&zip)?;
+    if Path::new(&unpacked).exists() {
+        fs::remove_dir_all(&unpacked)?;
+    }
+    match result? {
+        Ok(()) => Ok(CloneStatus::Cloned),
+        Err(reason) => fail(reason),
+    }
+}
+
+/// Checks the zipball against the registry's hash, if there is one, and unpacks it to `target`. Returns why it
+/// couldn't if it's broken
+fn unpack(
+    zip: &str,
+    unpacked: &str,
+    target: &str,
+    hash: Option<&str>,
+    url: &str,
+) -> Result<Result<(), String>, Error> {
+    if let Some(hash) = hash {
+        let output = match Command::new("sha1sum").arg(zip).output() {
+            Ok(output) => output,
+            Err(e) => return Ok(Err(format!("Can't run sha1sum, is it installed? ({e})"))),
+        };
+        let actual: String = String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        if !actual.eq_ignore_ascii_case(hash) {
+            return Ok(Err(format!(
+                "{url} has SHA-1 {actual}, the registry expects {hash}"
+            )));
+        }
+    }
+
+    let output = match Command::new("unzip")
+        .args(["-q", zip, "-d", unpacked])
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) => return Ok(Err(format!("Can't run unzip, is it installed? ({e})"))),
+    };
+    if !output.status.success() {
+        return Ok(Err(String::from_utf8_lossy(&output.stderr)
+            .trim_end()
+            .to_string()));
+    }
+
+    // GitHub puts everything under a single `{owner}-{repo}-{commit}` directory
+    match corpus::subdirectories(unpacked)?.as_slice() {
+        [top] => {
+            fs::rename(format!("{unpacked}/{top}"), target)?;
+            Ok(Ok(()))
+        }
+        _ => Ok(Err(format!(
+            "{url} doesn't have a single top level directory"
+        ))),
+    }
+}
+
+/// Asks GitHub what happened to a repository git can't find: renamed and transferred ones redirect to their new
+/// name. Returns `None` when GitHub can't tell, so the clone counts as a plain error and is retried
+fn look_up(
+    logger: &Logger,
+    runtime: &Handle,
+    client: &reqwest::Client,
+    repo: &str,
+) -> Option<Upstream> {
+    let response = runtime.block_on(
+        client
+            .get(format!("https://api.github.com/repos/{repo}"))
+            .header("User-Agent", "elm-dedup-project")
+            .send(),
     );
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            logger.error(format!(
+                "{} {}: {e}",
+                "!!! Error looking up".red(),
+                repo.blue()
+            ));
+            return None;
+        }
+    };
+
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Some(Upstream::Deleted);
+    }
+    if !response.status().is_success() {
+        logger.error(format!(
+            "{} {} ({})",
+            "!!! Error looking up".red(),
+            repo.blue(),
+            response.status()
+        ));
+        return None;
+    }
+
+    let found: GitHubRepo = runtime.block_on(response.json()).ok()?;
+    if found.full_name.eq_ignore_ascii_case(repo) {
+        // It's there, git just can't see it
+        None
+    } else {
+        Some(Upstream::MovedTo(found.full_name))
+    }
+}
+
+fn describe(upstream: &Upstream) -> String {
+    match upstream {
+        Upstream::Deleted => "(deleted)".to_string(),
+        Upstream::MovedTo(to) => {
+            format!("(moved to {to}, use --follow-renames to clone it from there)")
+        }
+    }
+}
+
+/// Downloads the docs.json and README.md the registry published for this version, unless they're already mirrored
+fn mirror_docs(
+    logger: &Logger,
+    options: &Options,
+    runtime: &Handle,
+    client: &reqwest::Client,
+    package: &PackageId,
+) -> Result<(), Error> {
+    let dir: String = package.docs_path();
+
+    for file in ["docs.json", "README.md"] {
+        let path: String = format!("{dir}/{file}");
+        if Path::new(&path).exists() {
+            continue;
+        }
+
+        let url: String = format!(
+            "{}/packages/{}/{}/{file}",
+            options.registry,
+            package.package_name(),
+            package.version
+        );
+        // Like missing docs, network errors don't fail the sync: the next --docs run retries them
+        let content = match runtime.block_on(async {
+            client
+                .get(&url)
+                .send()
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await
+        }) {
+            Ok(content) => content,
+            Err(e) => {
+                logger.error(format!(
+                    "{} {} {} ({e})",
+                    "!!! Error downloading".red(),
+                    file,
+                    package.to_string().blue()
+                ));
+                continue;
+            }
+        };
+        fs::create_dir_all(&dir)?;
+        fs::write(&path, content)?;
+        logger.verbose(format!(
+            "{} {} {}",
+            "Mirrored".green(),
+            file,
+            package.to_string().blue()
+        ));
+    }
 
     Ok(())
 }
+
+/// Runs git while cloning `what`, logging its output under that name. Returns git's error output if it failed
+fn git(logger: &Logger, what: &str, args: &[&str]) -> Result<Result<(), String>, Error> {
+    let output = Command::new("git").args(args).output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        logger.error(format!(
+            "{} {}\n{}",
+            "!!! Error cloning ".red(),
+            what.blue(),
+            stderr.trim_end()
+        ));
+
+        return Ok(Err(stderr.into_owned()));
+    }
+
+    if !stderr.trim().is_empty() {
+        logger.verbose(stderr.trim_end());
+    }
+
+    Ok(Ok(()))
+}
@@ -0,0 +1,163 @@
+use colored::*;
+use elm_dedup_project::{
+    corpus::{self, version_key},
+    error::Error,
+};
+use std::{collections::BTreeMap, fs};
+
+struct Licensing {
+    /// The `license` field of elm.json
+    declared: Option<String>,
+    /// The license the LICENSE file looks like, if there is one
+    file: Option<LicenseFile>,
+}
+
+enum LicenseFile {
+    Recognized(&'static str),
+    Unrecognized,
+}
+
+const LICENSE_FILES: [&str; 6] = [
+    "LICENSE",
+    "LICENSE.md",
+    "LICENSE.txt",
+    "LICENCE",
+    "COPYING",
+    "license",
+];
+
+/// Guesses the SPDX family of a license text from its usual boilerplate
+fn recognize(text: &str) -> Option<&'static str> {
+    let text: String = text.to_lowercase();
+    let patterns: [(&str, &'static str); 10] = [
+        ("gnu lesser general public license", "LGPL"),
+        ("gnu affero general public license", "AGPL"),
+        ("gnu general public license", "GPL"),
+        ("mozilla public license", "MPL"),
+        ("apache license", "Apache"),
+        ("this is free and unencumbered software", "Unlicense"),
+        ("permission to use, copy, modify, and/or distribute", "ISC"),
+        ("permission is hereby granted, free of charge", "MIT"),
+        ("neither the name", "BSD-3-Clause"),
+        (
+            "redistribution and use in source and binary forms",
+            "BSD-2-Clause",
+        ),
+    ];
+    patterns
+        .into_iter()
+        .find(|(pattern, _)| text.contains(pattern))
+        .map(|(_, license)| license)
+}
+
+fn read_licensing(path: &str) -> Licensing {
+    let declared: Option<String> = corpus::read_elm_json(path)
+        .ok()
+        .and_then(|elm_json| elm_json.license);
+
+    let file: Option<LicenseFile> = LICENSE_FILES
+        .iter()
+        .find_map(|name| fs::read_to_string(format!("{path}/{name}")).ok())
+        .map(|text| match recognize(&text) {
+            Some(license) => LicenseFile::Recognized(license),
+            None => LicenseFile::Unrecognized,
+        });
+
+    Licensing { declared, file }
+}
+
+fn main() -> Result<(), Error> {
+    if let Some(arg) = std::env::args().nth(1) {
+        return Err(format!("Unknown argument: {arg}").into());
+    }
+
+    // author/name -> version -> licensing
+    let mut packages: BTreeMap<String, BTreeMap<String, Licensing>> = BTreeMap::new();
+    for id in corpus::package_versions()? {
+        let licensing: Licensing = read_licensing(&id.path());
+        packages
+            .entry(id.package_name())
+            .or_default()
+            .insert(id.version, licensing);
+    }
+
+    let mut distribution: BTreeMap<String, usize> = BTreeMap::new();
+    let mut missing: Vec<String> = Vec::new();
+    let mut mismatched: Vec<String> = Vec::new();
+    let mut changes: Vec<String> = Vec::new();
+
+    for (package_name, versions) in &packages {
+        let mut versions: Vec<(&String, &Licensing)> = versions.iter().collect();
+        versions.sort_by_key(|(version, _)| version_key(version));
+
+        for (version, licensing) in &versions {
+            let id: String = format!("{package_name}@{version}");
+            match (&licensing.declared, &licensing.file) {
+                (None, _) => missing.push(format!("{id}: no license in elm.json")),
+                (Some(_), None) => missing.push(format!("{id}: no LICENSE file")),
+                (Some(declared), Some(LicenseFile::Recognized(file)))
+                    if !declared.starts_with(file) =>
+                {
+                    mismatched.push(format!(
+                        "{id}: elm.json says {declared}, LICENSE looks like {file}"
+                    ))
+                }
+                (Some(declared), Some(LicenseFile::Unrecognized)) => mismatched.push(format!(
+                    "{id}: elm.json says {declared}, LICENSE isn't recognized"
+                )),
+                (Some(_), Some(LicenseFile::Recognized(_))) => {}
+            }
+        }
+
+        for pair in versions.windows(2) {
+            let [(from_version, from), (to_version, to)] = pair else {
+                continue;
+            };
+            if from.declared != to.declared {
+                let show = |license: &Option<String>| {
+                    license.clone().unwrap_or_else(|| "none".to_string())
+                };
+                changes.push(format!(
+                    "{package_name} {from_version} -> {to_version}: {} -> {}",
+                    show(&from.declared),
+                    show(&to.declared)
+                ));
+            }
+        }
+
+        // The distribution counts each package once, by its latest version
+        if let Some((_, latest)) = versions.last() {
+            let license: String = latest
+                .declared
+                .clone()
+                .unwrap_or_else(|| "none".to_string());
+            *distribution.entry(license).or_default() += 1;
+        }
+    }
+
+    println!("{}", "License distribution (latest versions)".blue());
+    let mut distribution: Vec<(String, usize)> = distribution.into_iter().collect();
+    distribution.sort_by(|(a_license, a_count), (b_license, b_count)| {
+        b_count.cmp(a_count).then(a_license.cmp(b_license))
+    });
+    for (license, count) in &distribution {
+        println!("{count:6} {license}");
+    }
+
+    for (title, lines) in [
+        ("Missing licenses", &missing),
+        ("Ambiguous licenses", &mismatched),
+        ("License changes between versions", &changes),
+    ] {
+        println!(
+            "\n{} {}",
+            title.blue(),
+            format!("({})", lines.len()).dimmed()
+        );
+        for line in lines {
+            println!("  {line}");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use colored::*;
+use elm_dedup_project::{
+    corpus::{self, elm_files},
+    error::Error,
+    lock::WorkspaceLock,
+    package::PackageId,
+};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use serde::Serialize;
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// Stored next to the manifest, keyed by `author/name@version`
+fn metrics_path() -> String {
+    format!("{}/metrics.json", corpus::root())
+}
+
+#[derive(Serialize, Default)]
+struct Metrics {
+    /// Non-blank lines in the source directories
+    elm_loc: usize,
+    modules: usize,
+    exposed_modules: usize,
+    /// Non-blank lines under `tests/`
+    test_loc: usize,
+    test_modules: usize,
+}
+
+/// Counts the non-blank lines and the files of all the Elm modules under `dir`
+fn count(dir: &Path) -> Result<(usize, usize), Error> {
+    let files = elm_files(dir)?;
+    let mut loc: usize = 0;
+    for file in &files {
+        let content: String = String::from_utf8_lossy(&fs::read(file)?).into_owned();
+        loc += content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .count();
+    }
+    Ok((loc, files.len()))
+}
+
+fn measure(path: &str) -> Result<Metrics, Error> {
+    let mut metrics: Metrics = Metrics {
+        exposed_modules: corpus::exposed_modules(path)?.len(),
+        ..Metrics::default()
+    };
+
+    for source_directory in corpus::source_directories(path)? {
+        let (loc, modules) = count(&source_directory)?;
+        metrics.elm_loc += loc;
+        metrics.modules += modules;
+    }
+
+    (metrics.test_loc, metrics.test_modules) = count(&Path::new(path).join("tests"))?;
+    Ok(metrics)
+}
+
+fn main() -> Result<(), Error> {
+    let mut force: bool = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--force" => force = true,
+            _ => return Err(format!("Unknown argument: {arg}").into()),
+        }
+    }
+
+    let _lock: WorkspaceLock = WorkspaceLock::acquire(force)?;
+
+    println!("{}", "Measuring packages".blue());
+    let measured: Vec<(PackageId, Option<Metrics>)> = corpus::package_versions()?
+        .into_par_iter()
+        .map(|id: PackageId| {
+            let metrics: Option<Metrics> = match measure(&id.path()) {
+                Ok(metrics) => Some(metrics),
+                Err(e) => {
+                    println!("{} {id}: {e:?}", "!!! Error measuring".red());
+                    None
+                }
+            };
+            (id, metrics)
+        })
+        .collect();
+
+    let mut result: BTreeMap<String, Metrics> = BTreeMap::new();
+    for (id, metrics) in measured {
+        if let Some(metrics) = metrics {
+            result.insert(id.to_string(), metrics);
+        }
+    }
+
+    let path: String = metrics_path();
+    corpus::write_atomically(&path, serde_json::to_string_pretty(&result)?)?;
+
+    let total = |field: fn(&Metrics) -> usize| result.values().map(field).sum::<usize>();
+    println!(
+        "{}",
+        format!(
+            "Measured {} package versions: {} modules ({} exposed), {} lines of Elm, {} lines of tests",
+            result.len(),
+            total(|metrics| metrics.modules),
+            total(|metrics| metrics.exposed_modules),
+            total(|metrics| metrics.elm_loc),
+            total(|metrics| metrics.test_loc)
+        )
+        .green()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use colored::*;
+use elm_dedup_project::{
+    corpus::{self, subdirectories, ElmJson},
+    error::Error,
+    lock::WorkspaceLock,
+    manifest::Manifest,
+    package::PackageId,
+};
+use std::{fs, path::Path};
+
+fn main() -> Result<(), Error> {
+    let mut dry_run: bool = false;
+    let mut force: bool = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "--force" => force = true,
+            _ => return Err(format!("Unknown argument: {arg}").into()),
+        }
+    }
+
+    // A dry run only reads the corpus, so it can run next to other tools
+    let _lock: Option<WorkspaceLock> = if dry_run {
+        None
+    } else {
+        Some(WorkspaceLock::acquire(force)?)
+    };
+
+    let mut manifest: Manifest = Manifest::load()?;
+    let (mut migrated, mut present, mut skipped) = (0, 0, 0);
+
+    let root: &str = corpus::root();
+    for author in subdirectories(root)? {
+        let author_dir: String = format!("{root}/{author}");
+
+        // A run killed between the two renames below leaves the checkout in `.{name}.migrating`
+        let mut finished: Vec<String> = Vec::new();
+        for entry in fs::read_dir(&author_dir)? {
+            let file_name: String = entry?.file_name().to_string_lossy().into_owned();
+            let Some(name) = file_name
+                .strip_prefix('.')
+                .and_then(|name| name.strip_suffix(".migrating"))
+            else {
+                continue;
+            };
+
+            let package_name: String = format!("{author}/{name}");
+            let tmp_dir: String = format!("{author_dir}/{file_name}");
+            let id: PackageId = match release_in(&package_name, &tmp_dir) {
+                Ok(id) => id,
+                Err(reason) => {
+                    skip(&tmp_dir, &reason);
+                    skipped += 1;
+                    continue;
+                }
+            };
+            println!(
+                "{} {} {} {}",
+                "Finishing the move of".green(),
+                package_name.blue(),
+                "to".green(),
+                id.to_string().blue()
+            );
+            migrated += 1;
+            finished.push(name.to_string());
+
+            if !dry_run {
+                fs::create_dir_all(format!("{author_dir}/{name}"))?;
+                fs::rename(&tmp_dir, id.path())?;
+                manifest.insert(&id);
+            }
+        }
+
+        for name in subdirectories(&author_dir)? {
+            if finished.contains(&name) {
+                continue;
+            }
+
+            let package_name: String = format!("{author}/{name}");
+            let package_dir: String = format!("{root}/{package_name}");
+
+            if !Path::new(&package_dir).join("elm.json").exists() {
+                // Already in the `repos/{author}/{name}/{version}` layout
+                for version in subdirectories(&package_dir)? {
+                    manifest.insert(&PackageId {
+                        author: author.clone(),
+                        name: name.clone(),
+                        version,
+                    });
+                    present += 1;
+                }
+                continue;
+            }
+
+            let id: PackageId = match release_in(&package_name, &package_dir) {
+                Ok(id) => id,
+                Err(reason) => {
+                    skip(&package_name, &reason);
+                    skipped += 1;
+                    continue;
+                }
+            };
+            println!(
+                "{} {} {} {}",
+                "Moving".green(),
+                package_name.blue(),
+                "to".green(),
+                id.to_string().blue()
+            );
+            migrated += 1;
+
+            if dry_run {
+                continue;
+            }
+
+            // Move the checkout aside first, as its new home is inside its current path
+            let tmp_dir: String = format!("{author_dir}/.{name}.migrating");
+            fs::rename(&package_dir, &tmp_dir)?;
+            fs::create_dir(&package_dir)?;
+            fs::rename(&tmp_dir, id.path())?;
+
+            manifest.insert(&id);
+        }
+    }
+
+    if !dry_run {
+        manifest.save()?;
+    }
+
+    println!(
+        "{}",
+        format!("Migrated {migrated}, skipped {skipped}, already migrated {present}").green()
+    );
+
+    Ok(())
+}
+
+/// The release checked out in `dir`, from the version its elm.json declares, or why it can't be told
+fn release_in(package_name: &str, dir: &str) -> Result<PackageId, String> {
+    let elm_json: ElmJson =
+        corpus::read_elm_json(dir).map_err(|e| format!("can't read elm.json: {e}"))?;
+    let version: String = elm_json.version.ok_or("elm.json has no version")?;
+    PackageId::new(package_name, &version).ok_or(format!("{version} isn't a version"))
+}
+
+fn skip(what: &str, reason: &str) {
+    println!(
+        "{} {} {}",
+        "!!! Skipping".red(),
+        what.blue(),
+        format!("({reason})").red()
+    );
+}
@@ -1,89 +1,418 @@
+use colored::*;
+use elm_dedup_project::{corpus, error::Error, lock::WorkspaceLock, package::PackageId};
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use serde::Deserialize;
+use serde_json::{json, Value};
 use std::{
-    ffi::OsString,
-    fs::{self, ReadDir},
-    io,
+    fs,
+    path::{Path, PathBuf},
     process::Command,
     sync::atomic::{AtomicU32, Ordering},
 };
 
-#[derive(Debug)]
-enum Error {
-    IO(io::Error),
-    Other(String),
-    OsStringConversion(OsString),
+/// What `elm-review --report=json` prints. Besides `review-errors`, its type can say the config or the package
+/// failed to compile, in which case there are no findings to read
+#[derive(Deserialize)]
+struct Report {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    errors: Vec<FileFindings>,
 }
 
-impl From<io::Error> for Error {
-    fn from(e: io::Error) -> Self {
-        Error::IO(e)
+#[derive(Deserialize)]
+struct FileFindings {
+    path: String,
+    errors: Vec<Finding>,
+}
+
+#[derive(Deserialize)]
+struct Finding {
+    rule: String,
+    message: String,
+    region: Region,
+}
+
+#[derive(Deserialize)]
+struct Region {
+    start: Position,
+    end: Position,
+}
+
+#[derive(Deserialize)]
+struct Position {
+    line: u32,
+    column: u32,
+}
+
+/// SARIF base URIs, for the directories reviewed files are relative to
+const CORPUS_BASE: &str = "CORPUS";
+const APPS_BASE: &str = "APPS";
+
+/// A package release or an application to review
+struct Target {
+    /// `author/name@version`, or `owner/repo` for applications
+    name: String,
+    dir: String,
+    /// Which of `CORPUS_BASE` and `APPS_BASE` `dir` is in, and its path from there
+    base: &'static str,
+    relative: String,
+}
+
+impl Target {
+    fn package(id: PackageId) -> Target {
+        Target {
+            name: id.to_string(),
+            dir: id.path(),
+            base: CORPUS_BASE,
+            relative: format!("{}/{}/{}", id.author, id.name, id.version),
+        }
+    }
+
+    fn app(repo: String) -> Target {
+        Target {
+            dir: format!("{}/{repo}", corpus::apps_root()),
+            base: APPS_BASE,
+            relative: repo.clone(),
+            name: repo,
+        }
     }
 }
 
-impl From<String> for Error {
-    fn from(e: String) -> Self {
-        Error::Other(e)
+/// Declares a SARIF base URI for `dir`, as an absolute `file:` URI when it exists
+fn sarif_base(dir: &str, description: &str) -> Value {
+    match fs::canonicalize(dir) {
+        Ok(path) => json!({
+            "uri": format!(
+                "file://{}/",
+                path.display().to_string().replace('%', "%25").replace(' ', "%20")
+            ),
+            "description": { "text": description },
+        }),
+        Err(_) => json!({ "description": { "text": description } }),
     }
 }
 
-impl From<OsString> for Error {
-    fn from(e: OsString) -> Self {
-        Error::OsStringConversion(e)
+/// A SARIF result for a finding in `target`, with the file relative to its base
+fn sarif_result(target: &Target, path: &str, finding: &Finding) -> Value {
+    json!({
+        "ruleId": finding.rule,
+        "level": "warning",
+        "message": { "text": finding.message },
+        "locations": [{
+            "physicalLocation": {
+                "artifactLocation": {
+                    "uri": format!("{}/{path}", target.relative),
+                    "uriBaseId": target.base,
+                },
+                "region": {
+                    "startLine": finding.region.start.line,
+                    "startColumn": finding.region.start.column,
+                    "endLine": finding.region.end.line,
+                    "endColumn": finding.region.end.column,
+                },
+            },
+        }],
+        "properties": { "package": target.name },
+    })
+}
+
+/// Set on each elm-review run and inherited by whatever it starts, so processes it leaves behind can be found
+const RUN_MARKER: &str = "ELM_DEDUP_RUN";
+
+/// The processes still running with `RUN_MARKER` set to `marker`, as `pid command`
+fn leftovers(marker: &str) -> Vec<String> {
+    let variable: String = format!("{RUN_MARKER}={marker}");
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            let name = entry.file_name();
+            !name.is_empty() && name.to_string_lossy().chars().all(|c| c.is_ascii_digit())
+        })
+        .filter(|entry| {
+            // Other users' processes can't be read, and can't be ours anyway
+            fs::read(entry.path().join("environ")).is_ok_and(|environ| {
+                environ
+                    .split(|byte| *byte == 0)
+                    .any(|var| var == variable.as_bytes())
+            })
+        })
+        .map(|entry| {
+            let command: String = fs::read(entry.path().join("cmdline"))
+                .map(|cmdline| String::from_utf8_lossy(&cmdline).replace('\0', " "))
+                .unwrap_or_default();
+            format!("{} {}", entry.file_name().to_string_lossy(), command.trim())
+        })
+        .collect()
+}
+
+/// Runs download-repos on a packages list, preferring the one built alongside this tool
+fn download(list: &str, force: bool) -> Result<(), Error> {
+    let program: PathBuf = std::env::current_exe()?.with_file_name("download-repos");
+    let mut command: Command = if program.exists() {
+        Command::new(program)
+    } else {
+        Command::new("download-repos")
+    };
+    command.args(["--list", list]);
+    if force {
+        command.arg("--force");
+    }
+
+    if !command.status()?.success() {
+        println!(
+            "{}",
+            "download-repos failed, reviewing the packages that are there".yellow()
+        );
     }
+    Ok(())
 }
 
-#[tokio::main]
-async fn main() -> () {
-    println!("Getting repos list");
-    let authors: ReadDir = fs::read_dir("repos").unwrap();
-
-    let repos: Vec<String> = authors
-        .into_iter()
-        .flat_map(|author| {
-            let author = author.unwrap().file_name().into_string().unwrap();
-            fs::read_dir(format!("repos/{author}"))
-                .unwrap()
+/// The package releases to review: the listed ones or the whole corpus, narrowed by tag and patterns
+fn packages(
+    list: &Option<String>,
+    only_tag: &Option<String>,
+    filter: &corpus::Filter,
+) -> Result<Vec<PackageId>, Error> {
+    let repos: Vec<PackageId> = match list {
+        None => corpus::package_versions()?,
+        Some(list) => {
+            let (present, missing): (Vec<PackageId>, Vec<PackageId>) =
+                corpus::read_package_list(list)?
+                    .into_iter()
+                    .partition(|id| Path::new(&id.path()).exists());
+            if !missing.is_empty() {
+                println!(
+                    "{}",
+                    format!(
+                        "Skipping {} packages that couldn't be downloaded:",
+                        missing.len()
+                    )
+                    .yellow()
+                );
+                for id in &missing {
+                    println!("  {id}");
+                }
+            }
+            present
+        }
+    };
+
+    let repos: Vec<PackageId> = match only_tag {
+        None => repos,
+        Some(tag) => {
+            let features = corpus::load_features()?;
+            repos
                 .into_iter()
-                .map(|repo| {
-                    let repo = repo.unwrap().file_name().into_string().unwrap();
-                    format!("repos/{author}/{repo}")
+                .filter(|id| {
+                    features
+                        .get(&id.to_string())
+                        .is_some_and(|tags| tags.contains(tag))
                 })
-                .collect::<Vec<String>>()
-        })
-        .collect();
+                .collect()
+        }
+    };
 
+    let repos: Vec<PackageId> = if filter.is_empty() {
+        repos
+    } else {
+        let matching: Vec<PackageId> = repos
+            .iter()
+            .filter(|id| filter.matches(id))
+            .cloned()
+            .collect();
+        println!(
+            "Kept {} of {} packages matching --include and --exclude",
+            matching.len(),
+            repos.len()
+        );
+        matching
+    };
+
+    Ok(repos)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let mut force: bool = false;
+    let mut list: Option<String> = None;
+    let mut only_tag: Option<String> = None;
+    let mut sarif: Option<String> = None;
+    let mut apps: bool = false;
+    let mut filter: corpus::Filter = corpus::Filter::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--force" => force = true,
+            "--list" => list = Some(args.next().ok_or("--list needs a file".to_string())?),
+            "--only-tag" => {
+                only_tag = Some(args.next().ok_or("--only-tag needs a tag".to_string())?)
+            }
+            "--include" => filter
+                .include
+                .push(args.next().ok_or("--include needs a pattern".to_string())?),
+            "--exclude" => filter
+                .exclude
+                .push(args.next().ok_or("--exclude needs a pattern".to_string())?),
+            "--apps" => apps = true,
+            "--sarif" => sarif = Some(args.next().ok_or("--sarif needs a file".to_string())?),
+            _ => return Err(format!("Unknown argument: {arg}").into()),
+        }
+    }
+
+    if apps && (list.is_some() || only_tag.is_some() || !filter.is_empty()) {
+        return Err(
+            "--apps reviews every application, it can't be combined with --list, --only-tag, --include or --exclude"
+                .to_string()
+                .into(),
+        );
+    }
+
+    // download-repos takes the lock too, so it runs before this takes it
+    if let Some(list) = &list {
+        let missing: usize = corpus::read_package_list(list)?
+            .iter()
+            .filter(|id| !Path::new(&id.path()).exists())
+            .count();
+        if missing > 0 {
+            println!("Downloading {missing} listed packages that aren't downloaded yet");
+            download(list, force)?;
+        }
+    }
+
+    // elm-review writes elm-stuff inside each checkout
+    let _lock: WorkspaceLock = WorkspaceLock::acquire(force)?;
+
+    println!("Getting repos list");
+    let repos: Vec<Target> = if apps {
+        corpus::apps()?.into_iter().map(Target::app).collect()
+    } else {
+        packages(&list, &only_tag, &filter)?
+            .into_iter()
+            .map(Target::package)
+            .collect()
+    };
     println!("Got repos list");
 
     let home = std::env::home_dir()
-        .unwrap()
+        .ok_or("Could not find the home directory".to_string())?
         .into_os_string()
-        .into_string()
-        .unwrap();
+        .into_string()?;
 
     println!("Running elm-review");
 
     let total = repos.len();
     let done = AtomicU32::new(0);
+    let leaked = AtomicU32::new(0);
 
-    repos.into_par_iter().for_each(|path| {
-        let output: String = String::from_utf8(
-            Command::new("elm-review")
-                .args([
-                    "--config",
-                    &format!("{home}/src/elm-review-simplify/preview"),
-                ])
-                .current_dir(&path)
-                .output()
-                .unwrap()
-                .stdout,
-        )
-        .unwrap();
-        if output == "I found no errors!\n" {
-            let count = done.fetch_add(1, Ordering::AcqRel);
-            println!("{count:5}/{total}");
-            return;
-        }
+    let results: Vec<Vec<Value>> = repos
+        .into_par_iter()
+        .map(|target| {
+            let marker: String = format!("{}:{}", std::process::id(), target.name);
+            let config: String = format!("{home}/src/elm-review-simplify/preview");
+            let mut args: Vec<&str> = vec!["--config", &config];
+            if sarif.is_some() {
+                args.push("--report=json");
+            }
+            let output: String = String::from_utf8(
+                Command::new("elm-review")
+                    .args(&args)
+                    .current_dir(&target.dir)
+                    .env(RUN_MARKER, &marker)
+                    .output()
+                    .unwrap()
+                    .stdout,
+            )
+            .unwrap();
 
-        println!("\n\n==========================\n\n{path}\n\n{output}")
-    })
+            let leftovers: Vec<String> = leftovers(&marker);
+            if !leftovers.is_empty() {
+                leaked.fetch_add(1, Ordering::AcqRel);
+                println!(
+                    "{} {} left {} processes running:\n  {}",
+                    "!!! Leak:".yellow(),
+                    target.name,
+                    leftovers.len(),
+                    leftovers.join("\n  ")
+                );
+            }
+
+            if sarif.is_some() {
+                if let Ok(report) = serde_json::from_str::<Report>(&output) {
+                    if report.kind == "review-errors" {
+                        let count = done.fetch_add(1, Ordering::AcqRel);
+                        let results: Vec<Value> = report
+                            .errors
+                            .iter()
+                            .flat_map(|file| {
+                                file.errors
+                                    .iter()
+                                    .map(|finding| sarif_result(&target, &file.path, finding))
+                            })
+                            .collect();
+                        if results.is_empty() {
+                            println!("{count:5}/{total}");
+                        } else {
+                            println!(
+                                "{count:5}/{total} {}: {} findings",
+                                target.name,
+                                results.len()
+                            );
+                        }
+                        return results;
+                    }
+                }
+            } else if output == "I found no errors!\n" {
+                let count = done.fetch_add(1, Ordering::AcqRel);
+                println!("{count:5}/{total}");
+                return Vec::new();
+            }
+
+            println!(
+                "\n\n==========================\n\n{}\n\n{output}",
+                target.name
+            );
+            Vec::new()
+        })
+        .collect();
+
+    if let Some(sarif) = &sarif {
+        let results: Vec<Value> = results.into_iter().flatten().collect();
+        let log: Value = json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "elm-review",
+                        "informationUri": "https://package.elm-lang.org/packages/jfmengels/elm-review/latest/",
+                    },
+                },
+                "originalUriBaseIds": {
+                    CORPUS_BASE: sarif_base(corpus::root(), "The package corpus, by author/name/version"),
+                    APPS_BASE: sarif_base(&corpus::apps_root(), "The applications, by owner/repo"),
+                },
+                "results": results,
+            }],
+        });
+        fs::write(sarif, serde_json::to_string_pretty(&log)?)?;
+        println!(
+            "{}",
+            format!("Wrote {} findings to {sarif}", results.len()).green()
+        );
+    }
+
+    let leaked: u32 = leaked.into_inner();
+    if leaked > 0 {
+        println!(
+            "{}",
+            format!("{leaked} packages left processes running after elm-review exited").yellow()
+        );
+    }
+
+    Ok(())
 }
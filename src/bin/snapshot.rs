@@ -0,0 +1,213 @@
+use colored::*;
+use elm_dedup_project::{
+    corpus::{self, IGNORE_FILE},
+    error::Error,
+    lock::{WorkspaceLock, LOCK_FILE},
+    manifest::Manifest,
+};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::Path,
+    process::Command,
+};
+
+/// Snapshots keep the corpus under `repos/` wherever it's stored locally, so they restore anywhere
+const ARCHIVED_MANIFEST: &str = "repos/manifest.json";
+
+const USAGE: &str =
+    "Usage: snapshot (create|restore) <file.tar.gz> [--force]\n       snapshot fetch <url> <sha256> [--force]";
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let mut positional: Vec<String> = Vec::new();
+    let mut force: bool = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--force" => force = true,
+            _ if arg.starts_with('-') => return Err(format!("Unknown argument: {arg}").into()),
+            _ => positional.push(arg),
+        }
+    }
+
+    let _lock: WorkspaceLock = WorkspaceLock::acquire(force)?;
+
+    match positional.as_slice() {
+        [command, file] if command == "create" => create(file),
+        [command, file] if command == "restore" => restore(file),
+        [command, url, sha256] if command == "fetch" => fetch(url, sha256, force).await,
+        _ => Err(USAGE.to_string().into()),
+    }
+}
+
+/// Archives the corpus and its manifest. Entries are sorted and their metadata normalized, so the same corpus always
+/// produces the same bytes. Git metadata and build artifacts are left out
+fn create(file: &str) -> Result<(), Error> {
+    println!("{} {}", "Creating snapshot".green(), file.blue());
+
+    // Restoring needs the manifest, even for a corpus that predates it
+    let manifest: Manifest = Manifest::load()?;
+    manifest.save()?;
+
+    tar(&[
+        "--create",
+        "--file",
+        file,
+        "--use-compress-program=gzip -n",
+        "--sort=name",
+        "--mtime=@0",
+        "--owner=0",
+        "--group=0",
+        "--numeric-owner",
+        "--format=gnu",
+        "--exclude=.git",
+        "--exclude=elm-stuff",
+        "--exclude=node_modules",
+        &format!("--exclude=./{LOCK_FILE}"),
+        // Like .git, the bare repos can be fetched again. Docs and apps aren't part of the package corpus
+        "--exclude=./.bare",
+        "--exclude=./.docs",
+        "--exclude=./.apps",
+        &format!("--exclude-tag-all={IGNORE_FILE}"),
+        // Rename members only, leaving relative link targets alone
+        "--transform=s,^\\.,repos,SH",
+        "--directory",
+        corpus::root(),
+        ".",
+    ])?;
+
+    let packages: usize = corpus::package_versions()?.len();
+    println!(
+        "{}",
+        format!(
+            "Snapshotted {packages} package versions, sha256 {}",
+            sha256(file)?
+        )
+        .green()
+    );
+
+    Ok(())
+}
+
+/// Unpacks a snapshot next to the existing corpus, keeping any checkout that's already present, and merges its
+/// manifest into the local one
+fn restore(file: &str) -> Result<(), Error> {
+    println!("{} {}", "Restoring snapshot".green(), file.blue());
+
+    let snapshot_manifest = Command::new("tar")
+        .args([
+            "--extract",
+            "--gzip",
+            "--to-stdout",
+            "--file",
+            file,
+            ARCHIVED_MANIFEST,
+        ])
+        .output()?;
+    if !snapshot_manifest.status.success() {
+        return Err(format!("{file} doesn't contain {ARCHIVED_MANIFEST}").into());
+    }
+    let snapshot_manifest: Manifest = serde_json::from_slice(&snapshot_manifest.stdout)?;
+
+    tar(&[
+        "--extract",
+        "--gzip",
+        "--file",
+        file,
+        "--skip-old-files",
+        &format!("--exclude={ARCHIVED_MANIFEST}"),
+        "--strip-components=1",
+        "--directory",
+        corpus::root(),
+        "repos",
+    ])?;
+
+    let mut manifest: Manifest = Manifest::load()?;
+    let mut restored: usize = 0;
+    for (package_name, versions) in snapshot_manifest.packages {
+        restored += versions.len();
+        manifest
+            .packages
+            .entry(package_name)
+            .or_default()
+            .extend(versions);
+    }
+    manifest.save()?;
+
+    println!(
+        "{}",
+        format!("Restored {restored} package versions").green()
+    );
+
+    Ok(())
+}
+
+/// Downloads a published snapshot, checks it against the expected hash and restores it. The download is kept in the
+/// current directory, so a second fetch of the same snapshot (e.g. from a CI cache) skips the download. A file of the same
+/// name with another hash is only replaced with `--force`
+async fn fetch(url: &str, expected_sha256: &str, force: bool) -> Result<(), Error> {
+    let path: &str = url.split(['?', '#']).next().unwrap_or(url);
+    let file: &str = path
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .ok_or(format!("Can't find a file name in {url}"))?;
+
+    // sha256sum prints lowercase, but release pages may not
+    let expected_sha256: String = expected_sha256.to_ascii_lowercase();
+    let existing: Option<String> = if Path::new(file).exists() {
+        Some(sha256(file)?)
+    } else {
+        None
+    };
+    if existing.as_deref() == Some(expected_sha256.as_str()) {
+        println!("{} {}", "Already downloaded".green(), file.blue());
+    } else {
+        if let Some(existing) = &existing {
+            if !force {
+                return Err(format!(
+                    "{file} already exists with sha256 {existing}, not {expected_sha256}; pass --force to replace it"
+                )
+                .into());
+            }
+            println!("{} {}", "Replacing".yellow(), file.blue());
+        }
+        println!("{} {}", "Downloading".green(), url.blue());
+        let partial: String = format!("{file}.part");
+        let mut response = reqwest::get(url).await?.error_for_status()?;
+        let mut output: File = File::create(&partial)?;
+        while let Some(chunk) = response.chunk().await? {
+            output.write_all(&chunk)?;
+        }
+        output.sync_all()?;
+
+        let actual_sha256: String = sha256(&partial)?;
+        if actual_sha256 != expected_sha256 {
+            fs::remove_file(&partial)?;
+            return Err(format!(
+                "Checksum mismatch for {url}: expected {expected_sha256}, got {actual_sha256}"
+            )
+            .into());
+        }
+        fs::rename(&partial, file)?;
+    }
+
+    restore(file)
+}
+
+fn sha256(file: &str) -> Result<String, Error> {
+    let output = Command::new("sha256sum").arg(file).output()?;
+    let stdout: String = String::from_utf8_lossy(&output.stdout).into_owned();
+    match stdout.split_whitespace().next() {
+        Some(hash) if output.status.success() => Ok(hash.to_string()),
+        _ => Err(format!("sha256sum {file} failed").into()),
+    }
+}
+
+fn tar(args: &[&str]) -> Result<(), Error> {
+    let is_ok: bool = Command::new("tar").args(args).spawn()?.wait()?.success();
+    if !is_ok {
+        return Err(format!("tar {} failed", args.join(" ")).into());
+    }
+    Ok(())
+}
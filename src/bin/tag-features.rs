@@ -0,0 +1,180 @@
+use colored::*;
+use elm_dedup_project::{
+    corpus::{self, elm_files},
+    error::Error,
+    lock::WorkspaceLock,
+    package::PackageId,
+};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+};
+
+/// Features recognized by an import of one of their modules
+const IMPORTS: [(&str, &str); 8] = [
+    ("bytes", "import Bytes"),
+    ("json", "import Json."),
+    ("parser", "import Parser"),
+    ("random", "import Random"),
+    ("regex", "import Regex"),
+    ("webgl", "import WebGL"),
+    ("svg", "import Svg"),
+    ("kernel-code", "import Elm.Kernel."),
+];
+
+/// Whether `source` has a `{ r | field : ...` extensible record type, as opposed to a `{ r | field = ...` update
+fn has_extensible_record(source: &str) -> bool {
+    fn skip_identifier(s: &str) -> Option<&str> {
+        let s: &str = s.trim_start();
+        let end: usize = s
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(s.len());
+        if end == 0 || !s.starts_with(|c: char| c.is_lowercase()) {
+            return None;
+        }
+        Some(s[end..].trim_start())
+    }
+
+    source.match_indices('{').any(|(index, _)| {
+        skip_identifier(&source[index + 1..])
+            .and_then(|rest| rest.strip_prefix('|'))
+            .and_then(skip_identifier)
+            .is_some_and(|rest| rest.starts_with(':'))
+    })
+}
+
+fn tags_of(source: &str, tags: &mut BTreeSet<&'static str>) {
+    for (tag, import) in IMPORTS {
+        if source.lines().any(|line| line.starts_with(import)) {
+            tags.insert(tag);
+        }
+    }
+    if source.contains("Task.andThen") {
+        tags.insert("task-chains");
+    }
+    if source.lines().any(|line| line.starts_with("infix ")) {
+        tags.insert("custom-operators");
+    }
+    if has_extensible_record(source) {
+        tags.insert("extensible-records");
+    }
+}
+
+fn has_ports(source: &str) -> bool {
+    source
+        .lines()
+        .any(|line| line.starts_with("port module ") || line.starts_with("port "))
+}
+
+fn scan(path: &str) -> Result<BTreeSet<&'static str>, Error> {
+    let mut tags: BTreeSet<&'static str> = BTreeSet::new();
+
+    for source_directory in corpus::source_directories(path)? {
+        for file in elm_files(&source_directory)? {
+            let source: String = String::from_utf8_lossy(&fs::read(file)?).into_owned();
+            tags_of(&source, &mut tags);
+            if has_ports(&source) {
+                tags.insert("ports");
+            }
+        }
+    }
+
+    for file in elm_files(&Path::new(path).join("tests"))? {
+        let source: String = String::from_utf8_lossy(&fs::read(file)?).into_owned();
+        if has_ports(&source) {
+            tags.insert("ports-in-tests");
+        }
+    }
+
+    Ok(tags)
+}
+
+fn main() -> Result<(), Error> {
+    let mut force: bool = false;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--force" => force = true,
+            _ => return Err(format!("Unknown argument: {arg}").into()),
+        }
+    }
+
+    let _lock: WorkspaceLock = WorkspaceLock::acquire(force)?;
+
+    println!("{}", "Scanning packages".blue());
+    let scanned: Vec<(PackageId, Option<BTreeSet<&'static str>>)> = corpus::package_versions()?
+        .into_par_iter()
+        .map(|id: PackageId| match scan(&id.path()) {
+            Ok(tags) => (id, Some(tags)),
+            Err(e) => {
+                println!("{} {id}: {e:?}", "!!! Error scanning".red());
+                (id, None)
+            }
+        })
+        .collect();
+
+    let mut result: BTreeMap<String, BTreeSet<&'static str>> = BTreeMap::new();
+    for (id, tags) in scanned {
+        if let Some(tags) = tags {
+            result.insert(id.to_string(), tags);
+        }
+    }
+
+    let path: String = corpus::features_path();
+    corpus::write_atomically(&path, serde_json::to_string_pretty(&result)?)?;
+
+    let mut counts: BTreeMap<&'static str, usize> = BTreeMap::new();
+    for tag in result.values().flatten() {
+        *counts.entry(tag).or_default() += 1;
+    }
+    println!(
+        "{}",
+        format!("Tagged {} package versions", result.len()).green()
+    );
+    for (tag, count) in counts {
+        println!("{count:6} {tag}");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_extensible_record_types() {
+        assert!(has_extensible_record(
+            "type alias Named a =\n    { a | name : String }"
+        ));
+        assert!(has_extensible_record(
+            "view : { r | title : String, body : String } -> Html msg"
+        ));
+    }
+
+    #[test]
+    fn ignores_record_updates() {
+        assert!(!has_extensible_record(
+            "{ model | count = model.count + 1 }"
+        ));
+    }
+
+    #[test]
+    fn allows_any_whitespace() {
+        assert!(has_extensible_record("{r|name:String}"));
+        assert!(has_extensible_record(
+            "{ r\n    | name\n        : String\n    }"
+        ));
+        assert!(!has_extensible_record("{model|count=1}"));
+    }
+
+    #[test]
+    fn needs_a_lowercase_variable_and_field() {
+        assert!(!has_extensible_record("{ name : String }"));
+        assert!(!has_extensible_record("{ R | name : String }"));
+        assert!(!has_extensible_record("{ r | Name : String }"));
+        assert!(!has_extensible_record("{}"));
+        assert!(!has_extensible_record(""));
+    }
+}
@@ -0,0 +1,373 @@
+use crate::package::PackageId;
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Names the directory holding the checkouts when it isn't `repos/`, e.g. one on a bigger drive. The bare repos,
+/// mirrored docs and applications move along, inside it. Output keeps referring to releases as
+/// `author/name@version` either way
+pub const CORPUS_VAR: &str = "ELM_DEDUP_CORPUS";
+
+/// Where the checkouts, the manifest and the other corpus-wide files live
+pub fn root() -> &'static str {
+    static ROOT: OnceLock<String> = OnceLock::new();
+    ROOT.get_or_init(|| match std::env::var(CORPUS_VAR) {
+        Ok(dir) if !dir.is_empty() => dir.trim_end_matches('/').to_string(),
+        _ => "repos".to_string(),
+    })
+}
+
+/// A directory inside the corpus root, hidden so corpus walks don't take it for an author. It's on the same drive
+/// as the corpus and checked along with it by [`check_root`]
+fn reserved(name: &str) -> String {
+    format!("{}/.{name}", root())
+}
+
+/// Where download-repos --worktrees keeps one bare repo per package
+pub fn bare_root() -> String {
+    reserved("bare")
+}
+
+/// Where download-repos --docs mirrors the docs the registry published, by `author/name/version`
+pub fn docs_root() -> String {
+    reserved("docs")
+}
+
+/// Where download-repos --apps puts applications, by `owner/repo`
+pub fn apps_root() -> String {
+    reserved("apps")
+}
+
+/// Where `bare_root`, `docs_root` and `apps_root` were before they moved into the corpus
+pub const OLD_DIRECTORIES: [&str; 3] = ["bare", "docs", "apps"];
+
+/// Fails unless the corpus `CORPUS_VAR` points to is an existing directory, so an unmounted drive is noticed before
+/// a run fills the mount point or reports an empty corpus
+pub fn check_root() -> io::Result<()> {
+    if std::env::var(CORPUS_VAR).is_ok_and(|dir| !dir.is_empty()) && !Path::new(root()).is_dir() {
+        return Err(io::Error::other(format!(
+            "{CORPUS_VAR} is {}, which isn't a directory. Is the drive mounted?",
+            root()
+        )));
+    }
+    Ok(())
+}
+
+/// Subdirectories of `dir`, sorted, skipping files like the manifest and hidden directories
+pub fn subdirectories(dir: &str) -> io::Result<Vec<String>> {
+    let mut result: Vec<String> = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name: String = entry
+            .file_name()
+            .into_string()
+            .map_err(|name| io::Error::other(format!("Non UTF-8 directory name: {name:?}")))?;
+        if entry.file_type()?.is_dir() && !name.starts_with('.') {
+            result.push(name);
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+/// Dropping this file in an author, package or version directory excludes it from corpus walks
+pub const IGNORE_FILE: &str = ".elm-dedup-ignore";
+
+fn is_ignored(dir: &str) -> bool {
+    Path::new(dir).join(IGNORE_FILE).exists()
+}
+
+/// Whether corpus walks skip the release, because its author, package or version directory has an `IGNORE_FILE`
+pub fn ignores(id: &PackageId) -> bool {
+    let author_dir: String = format!("{}/{}", root(), id.author);
+    let package_dir: String = format!("{author_dir}/{}", id.name);
+    is_ignored(&author_dir) || is_ignored(&package_dir) || is_ignored(&id.path())
+}
+
+/// All the checked out releases, skipping ignored ones
+pub fn package_versions() -> io::Result<Vec<PackageId>> {
+    check_root()?;
+    let mut result: Vec<PackageId> = Vec::new();
+    for author in subdirectories(root())? {
+        let author_dir: String = format!("{}/{author}", root());
+        if is_ignored(&author_dir) {
+            continue;
+        }
+
+        for name in subdirectories(&author_dir)? {
+            let package_dir: String = format!("{author_dir}/{name}");
+            if is_ignored(&package_dir) {
+                continue;
+            }
+
+            for version in subdirectories(&package_dir)? {
+                if !is_ignored(&format!("{package_dir}/{version}")) {
+                    result.push(PackageId {
+                        author: author.clone(),
+                        name: name.clone(),
+                        version,
+                    });
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Which releases a run covers, from `--include` and `--exclude` patterns like `elm-community/*`. Patterns match
+/// the package name, or the whole `author/name@version` if they contain an `@`, and `*` matches anything
+#[derive(Default)]
+pub struct Filter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether `id` matches an include pattern, if there are any, and no exclude pattern
+    pub fn matches(&self, id: &PackageId) -> bool {
+        let matching = |pattern: &String| {
+            if pattern.contains('@') {
+                glob_match(pattern, &id.to_string())
+            } else {
+                glob_match(pattern, &id.package_name())
+            }
+        };
+        (self.include.is_empty() || self.include.iter().any(matching))
+            && !self.exclude.iter().any(matching)
+    }
+}
+
+fn glob_match(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((prefix, rest)) => {
+            let Some(text) = text.strip_prefix(prefix) else {
+                return false;
+            };
+            // Try every split of the text for the `*`, shortest first
+            (0..=text.len())
+                .filter(|index| text.is_char_boundary(*index))
+                .any(|index| glob_match(rest, &text[index..]))
+        }
+    }
+}
+
+/// The applications download-repos --apps cloned, as `owner/repo`, or none if it never ran
+pub fn apps() -> io::Result<Vec<String>> {
+    check_root()?;
+    let root: String = apps_root();
+    let mut result: Vec<String> = Vec::new();
+    if !Path::new(&root).is_dir() {
+        return Ok(result);
+    }
+
+    for owner in subdirectories(&root)? {
+        for repo in subdirectories(&format!("{root}/{owner}"))? {
+            result.push(format!("{owner}/{repo}"));
+        }
+    }
+    Ok(result)
+}
+
+/// Sort key for versions, ordering `1.10.0` after `1.9.0`
+pub fn version_key(version: &str) -> Vec<u64> {
+    version
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+/// All the `.elm` files under `dir`, sorted, skipping build artifacts. A missing `dir` has no files
+pub fn elm_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut result: Vec<PathBuf> = Vec::new();
+    if !dir.is_dir() {
+        return Ok(result);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path: PathBuf = entry.path();
+        let name = entry.file_name();
+        if entry.file_type()?.is_dir() {
+            if name != "elm-stuff" && name != "node_modules" && name != ".git" {
+                result.extend(elm_files(&path)?);
+            }
+        } else if path.extension().is_some_and(|extension| extension == "elm") {
+            result.push(path);
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+/// The first word of each line of a list file, skipping blank lines and `#` comments
+pub fn list_words(path: &str) -> io::Result<Vec<String>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|word| !word.starts_with('#'))
+        .map(|word| word.to_string())
+        .collect())
+}
+
+/// Reads `author/name@version` lines, e.g. copied from a report. Anything after the first word of a line is ignored,
+/// as are blank lines and `#` comments
+pub fn read_package_list(path: &str) -> io::Result<Vec<PackageId>> {
+    list_words(path)?
+        .iter()
+        .map(|word| {
+            word.trim_end_matches([':', ','])
+                .parse()
+                .map_err(|e| io::Error::other(format!("{path}: {e}")))
+        })
+        .collect()
+}
+
+/// The parts of elm.json the tools look at, for both packages and applications
+#[derive(Deserialize)]
+pub struct ElmJson {
+    #[serde(rename = "source-directories")]
+    pub source_directories: Option<Vec<String>>,
+    #[serde(rename = "exposed-modules")]
+    pub exposed_modules: Option<ExposedModules>,
+    pub license: Option<String>,
+    pub version: Option<String>,
+}
+
+/// Packages either list their exposed modules, or group them under headings
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum ExposedModules {
+    List(Vec<String>),
+    Grouped(BTreeMap<String, Vec<String>>),
+}
+
+pub fn read_elm_json(dir: &str) -> io::Result<ElmJson> {
+    serde_json::from_str(&fs::read_to_string(format!("{dir}/elm.json"))?).map_err(io::Error::other)
+}
+
+/// The source directories elm.json declares, or `src` for packages
+pub fn source_directories(dir: &str) -> io::Result<Vec<PathBuf>> {
+    Ok(read_elm_json(dir)?
+        .source_directories
+        .unwrap_or_else(|| vec!["src".to_string()])
+        .into_iter()
+        .map(|source_directory| Path::new(dir).join(source_directory))
+        .collect())
+}
+
+/// The modules a package exposes, empty for applications
+pub fn exposed_modules(dir: &str) -> io::Result<Vec<String>> {
+    Ok(match read_elm_json(dir)?.exposed_modules {
+        None => Vec::new(),
+        Some(ExposedModules::List(modules)) => modules,
+        Some(ExposedModules::Grouped(groups)) => groups.into_values().flatten().collect(),
+    })
+}
+
+/// Writes `path` through a temporary file next to it, so an interrupted write can't leave it truncated
+pub fn write_atomically(path: &str, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let tmp: String = format!("{path}.tmp");
+    fs::write(&tmp, contents)?;
+    fs::rename(tmp, path)
+}
+
+/// Written by tag-features, mapping `author/name@version` to the language features the package uses
+pub fn features_path() -> String {
+    format!("{}/features.json", root())
+}
+
+pub fn load_features() -> io::Result<BTreeMap<String, BTreeSet<String>>> {
+    let path: String = features_path();
+    let content: String = fs::read_to_string(&path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Can't read {path} ({e}), run tag-features first"),
+        )
+    })?;
+    serde_json::from_str(&content).map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(release: &str) -> PackageId {
+        release.parse().unwrap()
+    }
+
+    fn filter(include: &[&str], exclude: &[&str]) -> Filter {
+        Filter {
+            include: include.iter().map(|pattern| pattern.to_string()).collect(),
+            exclude: exclude.iter().map(|pattern| pattern.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn glob_without_star_matches_exactly() {
+        assert!(glob_match("elm/core", "elm/core"));
+        assert!(!glob_match("elm/core", "elm/core-extra"));
+        assert!(!glob_match("elm/core", "elm/cor"));
+    }
+
+    #[test]
+    fn glob_star_matches_anything() {
+        assert!(glob_match("elm-community/*", "elm-community/list-extra"));
+        assert!(glob_match("elm-community/*", "elm-community/"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*/core", "elm/core"));
+        assert!(!glob_match("elm-community/*", "elm/core"));
+    }
+
+    #[test]
+    fn glob_with_several_stars() {
+        assert!(glob_match("*/*-extra", "elm-community/list-extra"));
+        assert!(glob_match("a*b*c", "abc"));
+        assert!(glob_match("a*b*c", "a-b-b-c"));
+        assert!(!glob_match("a*b*c", "a-c-b"));
+        assert!(glob_match("**", "anything"));
+    }
+
+    #[test]
+    fn glob_handles_multi_byte_characters() {
+        assert!(glob_match("*é", "café"));
+        assert!(!glob_match("*e", "café"));
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        assert!(filter(&[], &[]).matches(&id("elm/core@1.0.5")));
+    }
+
+    #[test]
+    fn filter_patterns_match_the_package_name() {
+        let filter: Filter = filter(&["elm-community/*", "me/x"], &[]);
+        assert!(filter.matches(&id("elm-community/list-extra@8.7.0")));
+        assert!(filter.matches(&id("me/x@1.0.0")));
+        assert!(!filter.matches(&id("elm/core@1.0.5")));
+    }
+
+    #[test]
+    fn filter_patterns_with_an_at_match_the_release() {
+        let latest: Filter = filter(&["elm/*"], &["*@1.*"]);
+        assert!(latest.matches(&id("elm/core@2.0.0")));
+        assert!(!latest.matches(&id("elm/core@1.0.5")));
+        // Without an `@`, the version isn't part of what's matched
+        assert!(!filter(&["*1.0.5"], &[]).matches(&id("elm/core@1.0.5")));
+    }
+
+    #[test]
+    fn exclude_wins_over_include() {
+        let filter: Filter = filter(&["elm-community/*"], &["elm-community/list-extra"]);
+        assert!(!filter.matches(&id("elm-community/list-extra@8.7.0")));
+        assert!(filter.matches(&id("elm-community/maybe-extra@5.3.0")));
+    }
+}
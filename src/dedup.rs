@@ -0,0 +1,265 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+};
+
+/// What a file holds, identified by a hash of what a [`Backend`] kept of it
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct Content {
+    pub hash: u64,
+    pub size: u64,
+}
+
+pub fn content_of(bytes: &[u8]) -> Content {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    Content {
+        hash: hasher.finish(),
+        size: bytes.len() as u64,
+    }
+}
+
+/// A way of deciding which files count as the same: backends trade accuracy for speed
+pub trait Backend: Sync {
+    fn content(&self, path: &Path, bytes: &[u8]) -> Content;
+}
+
+/// Files are the same when their bytes are
+pub struct Exact;
+
+impl Backend for Exact {
+    fn content(&self, _path: &Path, bytes: &[u8]) -> Content {
+        content_of(bytes)
+    }
+}
+
+/// Elm modules are the same when their [`normalize_elm`]d sources are, other files when their bytes are
+pub struct Normalized;
+
+impl Backend for Normalized {
+    fn content(&self, path: &Path, bytes: &[u8]) -> Content {
+        if path.extension().is_some_and(|extension| extension == "elm") {
+            content_of(normalize_elm(&String::from_utf8_lossy(bytes)).as_bytes())
+        } else {
+            content_of(bytes)
+        }
+    }
+}
+
+pub const BACKENDS: [&str; 2] = ["exact", "normalized"];
+
+/// The backend called `name` on the command line, one of [`BACKENDS`]
+pub fn backend(name: &str) -> Option<Box<dyn Backend>> {
+    match name {
+        "exact" => Some(Box::new(Exact)),
+        "normalized" => Some(Box::new(Normalized)),
+        _ => None,
+    }
+}
+
+/// Every file of a checkout by its path relative to `dir`, skipping git metadata and build artifacts
+pub fn hash_tree(dir: &Path, backend: &dyn Backend) -> io::Result<BTreeMap<PathBuf, Content>> {
+    fn go(
+        root: &Path,
+        dir: &Path,
+        backend: &dyn Backend,
+        result: &mut BTreeMap<PathBuf, Content>,
+    ) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path: PathBuf = entry.path();
+            let name = entry.file_name();
+            if entry.file_type()?.is_dir() {
+                if name != ".git" && name != "elm-stuff" && name != "node_modules" {
+                    go(root, &path, backend, result)?;
+                }
+            } else if entry.file_type()?.is_file() {
+                let relative: PathBuf = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+                let content: Content = backend.content(&relative, &fs::read(&path)?);
+                result.insert(relative, content);
+            }
+        }
+        Ok(())
+    }
+
+    let mut result: BTreeMap<PathBuf, Content> = BTreeMap::new();
+    go(dir, dir, backend, &mut result)?;
+    Ok(result)
+}
+
+/// Elm source without comments, layout or the module declaration. Vendored modules are usually copied under a new
+/// module name, so this makes them hash the same as the module they were copied from
+pub fn normalize_elm(source: &str) -> String {
+    let code: String = strip_comments(source)
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+    let is_declared: bool = ["module ", "port module ", "effect module "]
+        .iter()
+        .any(|prefix| code.starts_with(prefix));
+    let Some(exposing) = code.find("exposing").filter(|_| is_declared) else {
+        return code;
+    };
+
+    // Skip the balanced parentheses of the exposing list
+    let mut depth: usize = 0;
+    for (index, c) in code[exposing..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 1 => return code[exposing + index + 1..].trim_start().to_string(),
+            ')' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    code
+}
+
+/// Removes `--` and nested `{- -}` comments, leaving string and char literals alone
+fn strip_comments(source: &str) -> String {
+    let chars: Vec<char> = source.chars().collect();
+    let mut result: String = String::with_capacity(source.len());
+    let mut i: usize = 0;
+    let starts_with = |i: usize, pattern: &str| {
+        pattern
+            .chars()
+            .enumerate()
+            .all(|(offset, c)| chars.get(i + offset) == Some(&c))
+    };
+
+    while i < chars.len() {
+        if starts_with(i, "--") {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if starts_with(i, "{-") {
+            let mut depth: usize = 0;
+            while i < chars.len() {
+                if starts_with(i, "{-") {
+                    depth += 1;
+                    i += 2;
+                } else if starts_with(i, "-}") {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+            result.push(' ');
+        } else if chars[i] == '"' || chars[i] == '\'' {
+            let quote: &str = if starts_with(i, "\"\"\"") {
+                "\"\"\""
+            } else if chars[i] == '"' {
+                "\""
+            } else {
+                "'"
+            };
+            result.push_str(quote);
+            i += quote.len();
+            while i < chars.len() && !starts_with(i, quote) {
+                if chars[i] == '\\' && i + 1 < chars.len() {
+                    result.push(chars[i]);
+                    i += 1;
+                }
+                result.push(chars[i]);
+                i += 1;
+            }
+            if i < chars.len() {
+                result.push_str(quote);
+                i += quote.len();
+            }
+        } else {
+            result.push(chars[i]);
+            i += 1;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_line_comments() {
+        assert_eq!(strip_comments("a = 1 -- one\nb = 2"), "a = 1 \nb = 2");
+    }
+
+    #[test]
+    fn strips_nested_block_comments() {
+        assert_eq!(strip_comments("a {- x {- y -} z -} b"), "a   b");
+        assert_eq!(strip_comments("{-| Docs -}\na = 1"), " \na = 1");
+    }
+
+    #[test]
+    fn keeps_comment_markers_in_strings() {
+        assert_eq!(strip_comments(r#"a = "-- {- -}""#), r#"a = "-- {- -}""#);
+        assert_eq!(strip_comments(r#"a = "\"--" -- c"#), r#"a = "\"--" "#);
+    }
+
+    #[test]
+    fn keeps_triple_quoted_strings() {
+        let source: &str = "a = \"\"\"say \"hi\" -- {- \"\"\" -- c";
+        assert_eq!(strip_comments(source), "a = \"\"\"say \"hi\" -- {- \"\"\" ");
+    }
+
+    #[test]
+    fn keeps_char_literals() {
+        assert_eq!(strip_comments("a = '-' -- c"), "a = '-' ");
+        assert_eq!(strip_comments("a = '\"' -- c"), "a = '\"' ");
+        assert_eq!(strip_comments(r"a = '\'' -- c"), r"a = '\'' ");
+    }
+
+    #[test]
+    fn normalize_drops_the_module_declaration() {
+        let source: &str = "module A exposing (Foo(..), bar)\n\nbar =\n    1\n";
+        assert_eq!(normalize_elm(source), "bar = 1");
+    }
+
+    #[test]
+    fn normalize_handles_port_and_effect_modules() {
+        assert_eq!(
+            normalize_elm("port module Ports exposing (send)\nport send : String -> Cmd msg"),
+            "port send : String -> Cmd msg"
+        );
+        assert_eq!(
+            normalize_elm(
+                "effect module Time where { subscription = MySub } exposing (every)\na = 1"
+            ),
+            "a = 1"
+        );
+    }
+
+    #[test]
+    fn normalize_keeps_sources_without_a_declaration() {
+        assert_eq!(normalize_elm("a =\n  1 -- one"), "a = 1");
+        assert_eq!(normalize_elm("x = exposing (a)"), "x = exposing (a)");
+    }
+
+    #[test]
+    fn renamed_copies_are_the_same_content() {
+        let original: &[u8] = b"module List.Extra exposing (last)\n\n{-| The last -}\nlast = List.head << List.reverse\n";
+        let copy: &[u8] = b"module Vendored.ListExtra exposing\n    ( last\n    )\n\nlast =\n    List.head << List.reverse\n";
+        let path: &Path = Path::new("src/A.elm");
+        assert_eq!(
+            Normalized.content(path, original),
+            Normalized.content(path, copy)
+        );
+        assert_ne!(Exact.content(path, original), Exact.content(path, copy));
+    }
+
+    #[test]
+    fn normalized_leaves_other_files_alone() {
+        let path: &Path = Path::new("README.md");
+        assert_ne!(
+            Normalized.content(path, b"a  b"),
+            Normalized.content(path, b"a b")
+        );
+    }
+}
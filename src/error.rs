@@ -0,0 +1,41 @@
+use std::{ffi::OsString, io};
+
+/// What the tools' `main` returns. It's only shown through `Debug`, when a tool fails
+#[derive(Debug)]
+pub enum Error {
+    Reqwest(reqwest::Error),
+    IO(io::Error),
+    Json(serde_json::Error),
+    Other(String),
+    OsStringConversion(OsString),
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Reqwest(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::IO(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+impl From<String> for Error {
+    fn from(e: String) -> Self {
+        Error::Other(e)
+    }
+}
+
+impl From<OsString> for Error {
+    fn from(e: OsString) -> Self {
+        Error::OsStringConversion(e)
+    }
+}
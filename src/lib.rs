@@ -0,0 +1,7 @@
+pub mod corpus;
+pub mod dedup;
+pub mod error;
+pub mod lock;
+pub mod manifest;
+pub mod package;
+pub mod report;
@@ -0,0 +1,121 @@
+use crate::corpus;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The lock's file name. It's hidden, so corpus walks skip it
+pub const LOCK_FILE: &str = ".elm-dedup.lock";
+
+pub fn lock_path() -> String {
+    format!("{}/{LOCK_FILE}", corpus::root())
+}
+
+#[derive(Serialize, Deserialize)]
+struct Owner {
+    command: String,
+    pid: u32,
+    host: String,
+    started: u64,
+}
+
+/// Held by every tool that mutates the corpus, so two of them can't clobber each other's checkouts and elm-stuff.
+/// That includes metrics and tag-features, which write their results into it. The reports that only read the
+/// corpus, like dedup-versions or doctor, don't take it. The lock file is removed on drop
+pub struct WorkspaceLock {
+    _private: (),
+}
+
+impl WorkspaceLock {
+    /// Takes the lock, or fails describing who holds it. `force` takes over a lock left behind by a killed run
+    pub fn acquire(force: bool) -> io::Result<WorkspaceLock> {
+        corpus::check_root()?;
+        fs::create_dir_all(corpus::root())?;
+
+        if force {
+            match fs::remove_file(lock_path()) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+
+        let mut file = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(lock_path())
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                return Err(io::Error::other(describe_owner()))
+            }
+            Err(e) => return Err(e),
+        };
+
+        let owner: Owner = Owner {
+            command: command_name(),
+            pid: std::process::id(),
+            host: host_name(),
+            started: now(),
+        };
+        let lock = WorkspaceLock { _private: () };
+        file.write_all(&serde_json::to_vec(&owner).map_err(io::Error::other)?)?;
+        Ok(lock)
+    }
+}
+
+impl Drop for WorkspaceLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(lock_path());
+    }
+}
+
+/// Who holds the lock, if anyone
+pub fn holder() -> Option<String> {
+    Path::new(&lock_path()).exists().then(describe_owner)
+}
+
+fn describe_owner() -> String {
+    let owner: Option<Owner> = fs::read_to_string(lock_path())
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok());
+    match owner {
+        Some(owner) => format!(
+            "The workspace is locked by {} (pid {} on {}, started {} minutes ago). If that run is gone, pass --force",
+            owner.command,
+            owner.pid,
+            owner.host,
+            now().saturating_sub(owner.started) / 60
+        ),
+        None => format!(
+            "The workspace is locked ({} exists). If no other run is active, pass --force",
+            lock_path()
+        ),
+    }
+}
+
+fn command_name() -> String {
+    std::env::args()
+        .next()
+        .and_then(|arg0| {
+            Path::new(&arg0)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn host_name() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|host| host.trim().to_string())
+        .unwrap_or_else(|_| "unknown host".to_string())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,65 @@
+use crate::{corpus, package::PackageId};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::Path,
+};
+
+/// Where the manifest lives, next to the checkouts it describes
+pub fn manifest_path() -> String {
+    format!("{}/manifest.json", corpus::root())
+}
+
+/// The list of package versions present in the corpus, keyed by `author/name`
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub packages: BTreeMap<String, BTreeSet<String>>,
+    /// How many releases of the registry were seen by the last incremental sync, for `all-packages/since/{since}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<usize>,
+    /// Packages whose GitHub repository is gone, keyed by `author/name`, so syncs stop retrying them. Remove an entry
+    /// to try again
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub gone: BTreeMap<String, Upstream>,
+}
+
+/// What happened to a repository git can no longer find
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Upstream {
+    Deleted,
+    /// Renamed or transferred, to this `owner/repo`
+    MovedTo(String),
+}
+
+impl Manifest {
+    /// Reads the manifest, or returns an empty one if it doesn't exist yet
+    pub fn load() -> io::Result<Manifest> {
+        let path: String = manifest_path();
+        if !Path::new(&path).exists() {
+            return Ok(Manifest::default());
+        }
+
+        let content: String = fs::read_to_string(&path)?;
+        serde_json::from_str(&content).map_err(io::Error::other)
+    }
+
+    /// Writes the manifest, see [`corpus::write_atomically`]
+    pub fn save(&self) -> io::Result<()> {
+        let content: String = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        let path: String = manifest_path();
+        if let Some(parent) = Path::new(&path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        corpus::write_atomically(&path, content)
+    }
+
+    pub fn insert(&mut self, id: &PackageId) {
+        self.packages
+            .entry(id.package_name())
+            .or_default()
+            .insert(id.version.clone());
+    }
+}
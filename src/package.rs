@@ -0,0 +1,145 @@
+use crate::corpus;
+use std::{fmt, str::FromStr};
+
+/// A release of a package, written `author/name@version` and checked out in `{root}/{author}/{name}/{version}`, where
+/// the root is [`corpus::root`]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct PackageId {
+    pub author: String,
+    pub name: String,
+    pub version: String,
+}
+
+/// Whether `part` can be an author or a package name. Leading dots are out, so names never leave or hide in the corpus
+pub fn is_name_part(part: &str) -> bool {
+    !part.is_empty()
+        && !part.starts_with('.')
+        && part
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-')
+}
+
+/// Whether `version` is a `major.minor.patch` version, the only kind the registry publishes
+pub fn is_version(version: &str) -> bool {
+    version.split('.').count() == 3
+        && version
+            .split('.')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+impl PackageId {
+    /// From a package name like `elm/core` and one of its versions. Lists and registry responses are parsed with
+    /// this, so it rejects anything that wouldn't map to a directory inside the corpus
+    pub fn new(package_name: &str, version: &str) -> Option<PackageId> {
+        let (author, name) = package_name.split_once('/')?;
+        if !is_name_part(author) || !is_name_part(name) || !is_version(version) {
+            return None;
+        }
+        Some(PackageId {
+            author: author.to_string(),
+            name: name.to_string(),
+            version: version.to_string(),
+        })
+    }
+
+    /// `author/name`, as the registry and GitHub know the package
+    pub fn package_name(&self) -> String {
+        format!("{}/{}", self.author, self.name)
+    }
+
+    /// Where the release is checked out
+    pub fn path(&self) -> String {
+        format!(
+            "{}/{}/{}/{}",
+            corpus::root(),
+            self.author,
+            self.name,
+            self.version
+        )
+    }
+
+    /// Where the docs the registry published for the release are mirrored
+    pub fn docs_path(&self) -> String {
+        format!(
+            "{}/{}/{}/{}",
+            corpus::docs_root(),
+            self.author,
+            self.name,
+            self.version
+        )
+    }
+}
+
+impl fmt::Display for PackageId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}@{}", self.author, self.name, self.version)
+    }
+}
+
+impl FromStr for PackageId {
+    type Err = String;
+
+    /// Parses `author/name@version`
+    fn from_str(s: &str) -> Result<PackageId, String> {
+        s.split_once('@')
+            .and_then(|(package_name, version)| PackageId::new(package_name, version))
+            .ok_or(format!("expected author/name@version, got {s}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_releases() {
+        let id: PackageId = "elm-community/list-extra@8.7.0".parse().unwrap();
+        assert_eq!(id.author, "elm-community");
+        assert_eq!(id.name, "list-extra");
+        assert_eq!(id.version, "8.7.0");
+        assert_eq!(id.to_string(), "elm-community/list-extra@8.7.0");
+        assert!("my_org/elm.json-decode@1.10.0".parse::<PackageId>().is_ok());
+    }
+
+    #[test]
+    fn rejects_malformed_releases() {
+        for release in [
+            "elm/core",
+            "elm@1.0.0",
+            "/core@1.0.0",
+            "elm/@1.0.0",
+            "elm/core/x@1.0.0",
+        ] {
+            assert!(release.parse::<PackageId>().is_err(), "{release}");
+        }
+    }
+
+    #[test]
+    fn rejects_names_leaving_the_corpus() {
+        for release in [
+            "../x@1.0.0",
+            "elm/..@1.0.0",
+            "./core@1.0.0",
+            ".hidden/core@1.0.0",
+            "elm/co re@1.0.0",
+        ] {
+            assert!(release.parse::<PackageId>().is_err(), "{release}");
+        }
+    }
+
+    #[test]
+    fn rejects_versions_that_are_not_versions() {
+        for version in [
+            "",
+            "1.0",
+            "1.0.0.0",
+            "1..0",
+            "v1.0.0",
+            "1.0.0-beta",
+            "../../../../tmp/evil",
+        ] {
+            assert!(PackageId::new("elm/core", version).is_none(), "{version}");
+        }
+        assert!(PackageId::new("elm/core", "10.0.12").is_some());
+    }
+}